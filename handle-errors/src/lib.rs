@@ -1,8 +1,16 @@
+use serde::Serialize;
 use tracing::{event, instrument, Level};
 use warp::{
     filters::{body::BodyDeserializeError, cors::CorsForbidden},
-    http::StatusCode,
-    reject::{Reject, MissingHeader},
+    http::{
+        header::{HeaderValue, CONTENT_TYPE},
+        StatusCode,
+    },
+    reject::{
+        InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader,
+        PayloadTooLarge, Reject, UnsupportedMediaType,
+    },
+    reply::Response,
     Rejection, Reply,
 };
 
@@ -20,12 +28,55 @@ pub struct ApiLayerError {
 pub enum Error {
     ParseError(std::num::ParseIntError),
     MissingParameters,
+    InvalidParameter(String),
+    /// Every field of the request which didn't pass validation
+    Validation(Vec<FieldError>),
+    /// What was looked for, e.g. `question 4`
+    NotFound(String),
+    /// Why the change clashes with the current state of the resource
+    Conflict(String),
+    /// Content type of a request body the route doesn't read
+    UnsupportedMediaType(String),
+    /// Why the request body can't be read
+    InvalidBody(String),
+    /// A change was sent without the `If-Match` header it needs
+    PreconditionRequired,
+    /// The resource was changed since the version named in `If-Match`
+    PreconditionFailed,
     WrongPassword,
     ArgonLibraryError(ArgonError),
     DatabaseQueryError(sqlx::Error),
     MigrationError(sqlx::migrate::MigrateError),
+    /// The server can't start with the configuration it was given
+    ConfigError(String),
     CannotDecryptToken,
+    /// No `Authorization` header was sent
+    MissingToken,
+    /// The `Authorization` header or the token in it can't be read
+    MalformedToken,
+    TokenExpired,
+    TokenNotYetValid,
+    /// The session of the token was ended by a logout or a password change
+    TokenRevoked,
     Unauthorized,
+    /// The request was made with an API key where only a login will do
+    ApiKeyNotAllowed,
+    /// The API key is unknown, has expired or was revoked
+    InvalidApiKey,
+    /// The API key is valid, only its scope doesn't cover the request
+    InsufficientScope,
+    CannotVoteOwnPost,
+    InvalidToken,
+    /// Seconds until the next login attempt is allowed
+    TooManyLoginAttempts(u64),
+    /// Seconds until the client gets to make another request
+    RateLimited(u64),
+    MailError(std::io::Error),
+    OidcNotConfigured,
+    /// The provider's reply could not be trusted or was an error
+    OidcLoginFailed(String),
+    TwoFactorNotConfigured,
+    InvalidTwoFactorCode,
     ExternalApiError(ReqwestError),
     ClientError(ApiLayerError),
     ServerError(ApiLayerError),
@@ -41,9 +92,29 @@ impl std::fmt::Display for ApiLayerError {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &*self {
+        match self {
             Error::ParseError(ref err) => write!(f, "Cannot parse parameter: {}", err),
             Error::MissingParameters => write!(f, "Missing parameter"),
+            Error::InvalidParameter(ref err) => write!(f, "Invalid parameter: {}", err),
+            Error::Validation(ref errors) => write!(
+                f,
+                "Invalid fields: {}",
+                errors
+                    .iter()
+                    .map(|error| format!("{} {}", error.field, error.message))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Error::NotFound(ref what) => write!(f, "Not found: {}", what),
+            Error::Conflict(ref err) => write!(f, "Conflict: {}", err),
+            Error::UnsupportedMediaType(ref content_type) => {
+                write!(f, "Unsupported content type: {}", content_type)
+            }
+            Error::InvalidBody(ref err) => write!(f, "Invalid request body: {}", err),
+            Error::PreconditionRequired => write!(f, "Missing If-Match header"),
+            Error::PreconditionFailed => {
+                write!(f, "Resource was changed since it was fetched")
+            }
             Error::WrongPassword => {
                 write!(f, "Wrong password")
             }
@@ -52,11 +123,35 @@ impl std::fmt::Display for Error {
             }
             Error::DatabaseQueryError(_) => write!(f, "Cannot update, invalid data."),
             Error::MigrationError(_) => write!(f, "Cannot migrate data"),
+            Error::ConfigError(ref err) => write!(f, "Invalid configuration: {}", err),
             Error::CannotDecryptToken => write!(f, "Cannot decrypt error"),
+            Error::MissingToken => write!(f, "Missing bearer token"),
+            Error::MalformedToken => write!(f, "Token is malformed"),
+            Error::TokenExpired => write!(f, "Token has expired"),
+            Error::TokenNotYetValid => write!(f, "Token is not valid yet"),
+            Error::TokenRevoked => write!(f, "Token has been revoked"),
             Error::Unauthorized => write!(
                 f, 
                 "No permission to change the underlying resource"
             ),
+            Error::ApiKeyNotAllowed => write!(f, "API keys cannot be used for this request"),
+            Error::InvalidApiKey => write!(f, "API key is unknown, expired or revoked"),
+            Error::InsufficientScope => write!(f, "The scope of the API key doesn't allow this request"),
+            Error::CannotVoteOwnPost => write!(f, "Cannot vote on your own post"),
+            Error::InvalidToken => write!(f, "Token is invalid or has expired"),
+            Error::RateLimited(seconds) => {
+                write!(f, "Too many requests, retry in {} seconds", seconds)
+            }
+            Error::TooManyLoginAttempts(seconds) => write!(
+                f,
+                "Too many failed login attempts, retry in {} seconds",
+                seconds
+            ),
+            Error::MailError(err) => write!(f, "Cannot send mail: {}", err),
+            Error::OidcNotConfigured => write!(f, "Single sign-on is not enabled"),
+            Error::OidcLoginFailed(err) => write!(f, "Single sign-on failed: {}", err),
+            Error::TwoFactorNotConfigured => write!(f, "Two-factor authentication is not enabled"),
+            Error::InvalidTwoFactorCode => write!(f, "Invalid two-factor code"),
             Error::ExternalApiError(err) => write!(f, "API call cannot be executed: {}", err),
             Error::ServerError(err) => write!(f, "External Server Error: {}", err),
             Error::ClientError(err) => write!(f, "External Client Error: {}", err),
@@ -73,93 +168,536 @@ impl std::fmt::Display for Error {
 impl Reject for Error {}
 impl Reject for ApiLayerError {}
 
-const DUPLICATE_KEY: u32 = 23505;
+const DUPLICATE_KEY: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+/// Failures a retry can get past, the transaction lost to another one
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+/// Protection space named in the `WWW-Authenticate` challenges
+const AUTH_REALM: &str = "rust-question-api";
+
+/// Status, code and message of the database failures caused by the request,
+/// or which the client can retry, the others are internal errors
+fn database_problem(error: &sqlx::Error) -> Option<(StatusCode, &'static str, &'static str)> {
+    let unavailable = Some((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "service_unavailable",
+        "Database is busy, retry later",
+    ));
+
+    match error {
+        sqlx::Error::RowNotFound => {
+            Some((StatusCode::NOT_FOUND, "not_found", "Resource not found"))
+        }
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed => unavailable,
+        sqlx::Error::Database(err) => match err.code().as_deref() {
+            Some(DUPLICATE_KEY) => Some((
+                StatusCode::CONFLICT,
+                "already_exists",
+                "Resource already exists",
+            )),
+            // The store answers inserts referencing a missing row with `NotFound`,
+            // what is left is a change to a row others still refer to
+            Some(FOREIGN_KEY_VIOLATION) => Some((
+                StatusCode::CONFLICT,
+                "conflict",
+                "Resource is still referenced by others",
+            )),
+            Some(SERIALIZATION_FAILURE) | Some(DEADLOCK_DETECTED) => unavailable,
+            // Class 08 is the connection to the database failing
+            Some(code) if code.starts_with("08") => unavailable,
+            // Classes 22 and 23 are data the request sent which the database refused
+            Some(code) if code.starts_with("22") || code.starts_with("23") => Some((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_data",
+                "Cannot update data",
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Error {
+    /// Status of the reply the error is answered with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::DatabaseQueryError(err) => database_problem(err)
+                .map(|(status, _, _)| status)
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Error::ParseError(_)
+            | Error::MissingParameters
+            | Error::InvalidParameter(_)
+            | Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::WrongPassword
+            | Error::MissingToken
+            | Error::MalformedToken
+            | Error::TokenExpired
+            | Error::TokenNotYetValid
+            | Error::TokenRevoked
+            | Error::Unauthorized
+            | Error::InvalidApiKey
+            | Error::OidcLoginFailed(_)
+            | Error::InvalidTwoFactorCode => StatusCode::UNAUTHORIZED,
+            Error::ApiKeyNotAllowed | Error::InsufficientScope | Error::CannotVoteOwnPost => {
+                StatusCode::FORBIDDEN
+            }
+            Error::InvalidToken => StatusCode::BAD_REQUEST,
+            Error::TooManyLoginAttempts(_) | Error::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::NotFound(_) | Error::OidcNotConfigured | Error::TwoFactorNotConfigured => {
+                StatusCode::NOT_FOUND
+            }
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::InvalidBody(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            Error::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Error::ArgonLibraryError(_)
+            | Error::MigrationError(_)
+            | Error::ConfigError(_)
+            | Error::CannotDecryptToken
+            | Error::MailError(_)
+            | Error::ExternalApiError(_)
+            | Error::ClientError(_)
+            | Error::ServerError(_)
+            | Error::ReqwestApiError(_)
+            | Error::MiddlewareReqwestApiError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 
+    /// Stable identifier of the error which clients can branch on,
+    /// unlike the message it never changes wording
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::DatabaseQueryError(err) => database_problem(err)
+                .map(|(_, code, _)| code)
+                .unwrap_or("internal_error"),
+            Error::ParseError(_) | Error::InvalidParameter(_) => "invalid_parameter",
+            Error::NotFound(_) => "not_found",
+            Error::Conflict(_) => "conflict",
+            Error::UnsupportedMediaType(_) => "unsupported_media_type",
+            Error::InvalidBody(_) => "invalid_body",
+            Error::PreconditionRequired => "precondition_required",
+            Error::PreconditionFailed => "precondition_failed",
+            Error::MissingParameters => "missing_parameter",
+            Error::Validation(_) => "validation_failed",
+            Error::WrongPassword => "wrong_credentials",
+            Error::MissingToken => "missing_token",
+            Error::MalformedToken => "malformed_token",
+            Error::TokenExpired => "token_expired",
+            Error::TokenNotYetValid => "token_not_yet_valid",
+            Error::TokenRevoked => "token_revoked",
+            Error::Unauthorized => "unauthorized",
+            Error::ApiKeyNotAllowed => "api_key_not_allowed",
+            Error::InvalidApiKey => "invalid_api_key",
+            Error::InsufficientScope => "insufficient_scope",
+            Error::CannotVoteOwnPost => "cannot_vote_own_post",
+            Error::InvalidToken => "invalid_token",
+            Error::TooManyLoginAttempts(_) => "too_many_login_attempts",
+            Error::RateLimited(_) => "rate_limited",
+            Error::OidcNotConfigured => "sso_not_enabled",
+            Error::OidcLoginFailed(_) => "sso_failed",
+            Error::TwoFactorNotConfigured => "two_factor_not_enabled",
+            Error::InvalidTwoFactorCode => "invalid_two_factor_code",
+            Error::ExternalApiError(_)
+            | Error::ClientError(_)
+            | Error::ServerError(_)
+            | Error::ReqwestApiError(_)
+            | Error::MiddlewareReqwestApiError(_) => "external_service_error",
+            Error::ArgonLibraryError(_)
+            | Error::MigrationError(_)
+            | Error::ConfigError(_)
+            | Error::CannotDecryptToken
+            | Error::MailError(_) => "internal_error",
+        }
+    }
+
+    /// What the client gets to read, failures on our side don't give away details
+    fn public_message(&self) -> String {
+        match self {
+            Error::WrongPassword => "Wrong E-Mail/Password combination".to_string(),
+            Error::DatabaseQueryError(err) => database_problem(err)
+                .map(|(_, _, message)| message)
+                .unwrap_or("Internal Server Error")
+                .to_string(),
+            _ if self.status().is_server_error() => "Internal Server Error".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// A field of the request body or query which didn't pass validation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Body of every error reply, the problem details of RFC 7807 along with
+/// a stable `code` and the id of the request to look it up in the logs
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub code: &'static str,
+    pub message: String,
+    pub request_id: String,
+    /// Field-level details, only sent when the request had invalid fields
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    pub fn new(
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+        request_id: &str,
+    ) -> Problem {
+        Problem {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            code,
+            message,
+            request_id: request_id.to_string(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn from_error(error: &Error, request_id: &str) -> Problem {
+        let mut problem = Problem::new(
+            error.status(),
+            error.code(),
+            error.public_message(),
+            request_id,
+        );
+        if let Error::Validation(errors) = error {
+            problem.errors = errors.clone();
+        }
+        problem
+    }
+
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut res = warp::reply::with_status(warp::reply::json(&self), status).into_response();
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        res
+    }
+}
+
+/// `WWW-Authenticate` header of a reply to a request without a usable
+/// bearer token or API key, as described in RFC 6750
+fn www_authenticate(error: &Error) -> String {
+    match error {
+        Error::MissingToken => format!("Bearer realm=\"{}\"", AUTH_REALM),
+        Error::InvalidApiKey => format!(
+            "ApiKey realm=\"{}\", error=\"invalid_token\", error_description=\"{}\"",
+            AUTH_REALM, error
+        ),
+        _ => format!(
+            "Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"{}\"",
+            AUTH_REALM, error
+        ),
+    }
+}
+
+/// Turns the rejection of a request into a problem+json reply
 #[instrument]
-pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
-    if let Some(crate::Error::DatabaseQueryError(e)) = r.find() {
-        event!(Level::ERROR, "Database query error");
-        match e {
-            sqlx::Error::Database(err) => {
-                if err.code().unwrap().parse::<u32>().unwrap() == DUPLICATE_KEY {
-                    Ok(warp::reply::with_status(
-                        "Account already exists".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
-                } else {
-                    Ok(warp::reply::with_status(
-                        "Cannot update data".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
+pub async fn return_error(r: Rejection, request_id: String) -> Result<Response, Rejection> {
+    if let Some(error) = r.find::<Error>() {
+        if error.status().is_server_error() {
+            event!(Level::ERROR, "{:?}", error);
+        } else {
+            event!(Level::WARN, "{}", error);
+        }
+
+        let mut res = Problem::from_error(error, &request_id).into_response();
+        match error {
+            Error::TooManyLoginAttempts(seconds) | Error::RateLimited(seconds) => {
+                res.headers_mut()
+                    .insert("Retry-After", HeaderValue::from(*seconds));
+            }
+            Error::MissingToken
+            | Error::MalformedToken
+            | Error::TokenExpired
+            | Error::TokenNotYetValid
+            | Error::TokenRevoked
+            | Error::InvalidApiKey => {
+                if let Ok(challenge) = HeaderValue::from_str(&www_authenticate(error)) {
+                    res.headers_mut().insert("WWW-Authenticate", challenge);
                 }
             }
-            _ => Ok(warp::reply::with_status(
-                "Cannot update data".to_string(),
-                StatusCode::UNPROCESSABLE_ENTITY,
-            )),
+            _ => {}
         }
-    } else if let Some(crate::Error::ClientError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status(
-            "Internal Server Error".to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        ))
-    } else if let Some(crate::Error::ServerError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status(
-            "Internal Server Error".to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        ))
-    } else if let Some(Error::ExternalApiError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status(
-            "internal server error".to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        ))
-    } else if let Some(crate::Error::MiddlewareReqwestApiError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status(
-            "Internal Server Error".to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        ))
-    } else if let Some(crate::Error::WrongPassword) = r.find() {
-        event!(Level::ERROR, "Entered wrong password");
-        Ok(warp::reply::with_status(
-            "Wrong E-Mail/Password combination".to_string(),
-            StatusCode::UNAUTHORIZED,
-        ))
-    } else if let Some(crate::Error::Unauthorized) = r.find() {
-        event!(Level::ERROR, "Not matching account id");
-        Ok(warp::reply::with_status(
-            "No permission to change underlying resource".to_string(),
-            StatusCode::UNAUTHORIZED,
-        ))
+        Ok(res)
     } else if let Some(error) = r.find::<CorsForbidden>() {
         event!(Level::ERROR, "CORS forbidden error: {}", error);
-        Ok(warp::reply::with_status(
-            error.to_string(),
+        Ok(Problem::new(
             StatusCode::FORBIDDEN,
-        ))
+            "cors_forbidden",
+            error.to_string(),
+            &request_id,
+        )
+        .into_response())
     } else if let Some(error) = r.find::<BodyDeserializeError>() {
         event!(Level::ERROR, "Cannot deserizalize request body: {}", error);
-        Ok(warp::reply::with_status(
-            error.to_string(),
+        Ok(Problem::new(
             StatusCode::UNPROCESSABLE_ENTITY,
-        ))
-    } else if let Some(error) = r.find::<Error>() {
-        event!(Level::ERROR, "{}", error);
-        Ok(warp::reply::with_status(
+            "invalid_body",
             error.to_string(),
-            StatusCode::UNPROCESSABLE_ENTITY,
-        ))
+            &request_id,
+        )
+        .into_response())
     } else if let Some(error) = r.find::<MissingHeader>() {
         event!(Level::ERROR, "{}", error);
-        Ok(warp::reply::with_status(error.to_string(), StatusCode::BAD_REQUEST))
-    } else {
+        Ok(Problem::new(
+            StatusCode::BAD_REQUEST,
+            "missing_header",
+            error.to_string(),
+            &request_id,
+        )
+        .into_response())
+    } else if let Some((status, code, message)) = rejection_problem(&r) {
+        event!(Level::WARN, "{}", message);
+        Ok(Problem::new(status, code, message, &request_id).into_response())
+    } else if r.is_not_found() {
         event!(Level::WARN, "Requested route was not found");
-        Ok(warp::reply::with_status(
-            "Route not found".to_string(),
+        Ok(Problem::new(
             StatusCode::NOT_FOUND,
+            "route_not_found",
+            "Route not found".to_string(),
+            &request_id,
+        )
+        .into_response())
+    } else {
+        event!(Level::ERROR, "Unhandled rejection: {:?}", r);
+        Ok(Problem::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Internal Server Error".to_string(),
+            &request_id,
+        )
+        .into_response())
+    }
+}
+
+/// Status, code and message of the rejections warp makes on its own. Routes
+/// match the path before the method, so a wrong method is only reported for
+/// a path which exists
+fn rejection_problem(r: &Rejection) -> Option<(StatusCode, &'static str, String)> {
+    if let Some(error) = r.find::<InvalidQuery>() {
+        Some((StatusCode::BAD_REQUEST, "invalid_query", error.to_string()))
+    } else if let Some(error) = r.find::<InvalidHeader>() {
+        Some((StatusCode::BAD_REQUEST, "invalid_header", error.to_string()))
+    } else if let Some(error) = r.find::<LengthRequired>() {
+        Some((StatusCode::LENGTH_REQUIRED, "length_required", error.to_string()))
+    } else if let Some(error) = r.find::<PayloadTooLarge>() {
+        Some((StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", error.to_string()))
+    } else if let Some(error) = r.find::<UnsupportedMediaType>() {
+        Some((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            error.to_string(),
         ))
+    } else {
+        r.find::<MethodNotAllowed>()
+            .map(|error| (StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use std::{borrow::Cow, error::Error as StdError, fmt};
+
+    use super::{www_authenticate, Error, FieldError, Problem, StatusCode};
+
+    /// A failure reported by Postgres with the given SQLSTATE code
+    #[derive(Debug)]
+    struct PgFailure(&'static str);
+
+    impl fmt::Display for PgFailure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "database failure {}", self.0)
+        }
+    }
+
+    impl StdError for PgFailure {}
+
+    impl sqlx::error::DatabaseError for PgFailure {
+        fn message(&self) -> &str {
+            "database failure"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+    }
+
+    fn database(code: &'static str) -> Error {
+        Error::DatabaseQueryError(sqlx::Error::Database(Box::new(PgFailure(code))))
+    }
+
+    fn answer(error: Error) -> (StatusCode, &'static str) {
+        (error.status(), error.code())
+    }
+
+    #[test]
+    fn errors_have_status_and_code() {
+        assert_eq!(
+            answer(Error::NotFound("question 4".to_string())),
+            (StatusCode::NOT_FOUND, "not_found")
+        );
+        assert_eq!(
+            answer(Error::Conflict("question 4 still has answers".to_string())),
+            (StatusCode::CONFLICT, "conflict")
+        );
+        assert_eq!(
+            answer(Error::Validation(Vec::new())),
+            (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+        );
+        assert_eq!(
+            answer(Error::PreconditionRequired),
+            (StatusCode::PRECONDITION_REQUIRED, "precondition_required")
+        );
+        assert_eq!(
+            answer(Error::PreconditionFailed),
+            (StatusCode::PRECONDITION_FAILED, "precondition_failed")
+        );
+        assert_eq!(
+            answer(Error::UnsupportedMediaType("text/plain".to_string())),
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type")
+        );
+        assert_eq!(
+            answer(Error::WrongPassword),
+            (StatusCode::UNAUTHORIZED, "wrong_credentials")
+        );
+        assert_eq!(
+            answer(Error::TokenRevoked),
+            (StatusCode::UNAUTHORIZED, "token_revoked")
+        );
+        assert_eq!(
+            answer(Error::ApiKeyNotAllowed),
+            (StatusCode::FORBIDDEN, "api_key_not_allowed")
+        );
+        assert_eq!(
+            answer(Error::InvalidApiKey),
+            (StatusCode::UNAUTHORIZED, "invalid_api_key")
+        );
+        assert_eq!(
+            answer(Error::InsufficientScope),
+            (StatusCode::FORBIDDEN, "insufficient_scope")
+        );
+        assert_eq!(
+            answer(Error::RateLimited(30)),
+            (StatusCode::TOO_MANY_REQUESTS, "rate_limited")
+        );
+        assert_eq!(
+            answer(Error::MailError(std::io::ErrorKind::Other.into())),
+            (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+        );
+    }
+
+    #[test]
+    fn database_failures_caused_by_the_request() {
+        assert_eq!(
+            answer(Error::DatabaseQueryError(sqlx::Error::RowNotFound)),
+            (StatusCode::NOT_FOUND, "not_found")
+        );
+        assert_eq!(
+            answer(database("23505")),
+            (StatusCode::CONFLICT, "already_exists")
+        );
+        assert_eq!(answer(database("23503")), (StatusCode::CONFLICT, "conflict"));
+        assert_eq!(
+            answer(database("23514")),
+            (StatusCode::UNPROCESSABLE_ENTITY, "invalid_data")
+        );
+        assert_eq!(
+            answer(database("22P02")),
+            (StatusCode::UNPROCESSABLE_ENTITY, "invalid_data")
+        );
+    }
+
+    #[test]
+    fn database_failures_on_our_side() {
+        assert_eq!(
+            answer(database("40001")),
+            (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable")
+        );
+        assert_eq!(
+            answer(database("40P01")),
+            (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable")
+        );
+        assert_eq!(
+            answer(database("08006")),
+            (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable")
+        );
+        assert_eq!(
+            answer(Error::DatabaseQueryError(sqlx::Error::PoolTimedOut)),
+            (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable")
+        );
+        assert_eq!(
+            answer(database("42P01")),
+            (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+        );
+        assert_eq!(
+            answer(database("53100")),
+            (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+        );
+    }
+
+    #[test]
+    fn problems_keep_server_details_private() {
+        let problem = Problem::from_error(&database("42P01"), "id-1");
+        assert_eq!(problem.status, 500);
+        assert_eq!(problem.message, "Internal Server Error");
+        assert_eq!(problem.request_id, "id-1");
+
+        let field = FieldError {
+            field: "title".to_string(),
+            message: "must not be empty".to_string(),
+        };
+        let problem = Problem::from_error(&Error::Validation(vec![field.clone()]), "id-2");
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.errors, vec![field]);
+    }
+
+    #[test]
+    fn challenges_name_the_token_problem() {
+        assert_eq!(
+            www_authenticate(&Error::MissingToken),
+            "Bearer realm=\"rust-question-api\""
+        );
+        assert_eq!(
+            www_authenticate(&Error::TokenExpired),
+            "Bearer realm=\"rust-question-api\", error=\"invalid_token\", \
+            error_description=\"Token has expired\""
+        );
+        assert_eq!(
+            www_authenticate(&Error::InvalidApiKey),
+            "ApiKey realm=\"rust-question-api\", error=\"invalid_token\", \
+            error_description=\"API key is unknown, expired or revoked\""
+        );
     }
 }
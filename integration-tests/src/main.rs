@@ -28,7 +28,10 @@ struct QuestionAnswer {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct Token(String);
+struct Token {
+    access_token: String,
+    refresh_token: String,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), handle_errors::Error> {
@@ -61,7 +64,7 @@ async fn main() -> Result<(), handle_errors::Error> {
     let store = setup_store(&config).await?;
 
     // start the server and listen for a sender signal to shut it down
-    let handler = oneshot(store).await;
+    let handler = oneshot(&config, store).await;
 
     // create a test user to use throughout the tests
     let u = User {
@@ -94,7 +97,34 @@ async fn main() -> Result<(), handle_errors::Error> {
     }
     
     print!("Running post_question...");
-    match std::panic::AssertUnwindSafe(post_question(token)).catch_unwind().await {
+    match std::panic::AssertUnwindSafe(post_question(token.clone())).catch_unwind().await {
+        Ok(_) => println!("✓"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running read_api_key_only_gets...");
+    match std::panic::AssertUnwindSafe(read_api_key_only_gets(token.clone())).catch_unwind().await {
+        Ok(_) => println!("✓"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running write_api_key_cannot_manage_account...");
+    match std::panic::AssertUnwindSafe(write_api_key_cannot_manage_account(token.clone())).catch_unwind().await {
+        Ok(_) => println!("✓"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running logout_revokes_token...");
+    match std::panic::AssertUnwindSafe(logout_revokes_token(token)).catch_unwind().await {
         Ok(_) => println!("✓"),
         Err(_) => {
             let _ = handler.sender.send(1);
@@ -115,7 +145,7 @@ async fn register_new_user(user: &User) {
         .send()
         .await
         .unwrap()
-        .json::<Value>()
+        .text()
         .await;
 
     assert_eq!(res.unwrap(), "Account added".to_string());
@@ -148,7 +178,7 @@ async fn post_question(token: Token) {
     let client = reqwest::Client::new();
     let res = client
         .post("http://localhost:8080/questions")
-        .header("Authorization", token.0)
+        .header("Authorization", format!("Bearer {}", token.access_token))
         .json(&q)
         .send()
         .await
@@ -160,3 +190,135 @@ async fn post_question(token: Token) {
     assert_eq!(res.id, 1);
     assert_eq!(res.title, q.title);
 }
+
+async fn logout_revokes_token(token: Token) {
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://localhost:8080/logout")
+        .header("Authorization", format!("Bearer {}", token.access_token))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 200);
+
+    // The token is still signed and unexpired, only the session behind it ended
+    let q = Question {
+        title: "Second Question".to_string(),
+        content: "Am I still logged in?".to_string(),
+    };
+
+    let res = client
+        .post("http://localhost:8080/questions")
+        .header("Authorization", format!("Bearer {}", token.access_token))
+        .json(&q)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 401);
+    assert_eq!(res.json::<Value>().await.unwrap()["code"], "token_revoked");
+}
+
+async fn read_api_key_only_gets(token: Token) {
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://localhost:8080/accounts/me/api-keys")
+        .header("Authorization", format!("Bearer {}", token.access_token))
+        .json(&serde_json::json!({ "name": "ci", "scope": "read" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 200);
+    let key = res.json::<Value>().await.unwrap()["key"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = client
+        .get("http://localhost:8080/accounts/me")
+        .header("Authorization", format!("ApiKey {}", key))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 200);
+
+    let q = Question {
+        title: "Read Only".to_string(),
+        content: "Can a read key post?".to_string(),
+    };
+
+    let res = client
+        .post("http://localhost:8080/questions")
+        .header("Authorization", format!("ApiKey {}", key))
+        .json(&q)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 403);
+    assert_eq!(res.json::<Value>().await.unwrap()["code"], "insufficient_scope");
+
+    let res = client
+        .get("http://localhost:8080/accounts/me")
+        .header("Authorization", "ApiKey qa_unknown")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 401);
+    assert!(res.headers()["WWW-Authenticate"]
+        .to_str()
+        .unwrap()
+        .starts_with("ApiKey "));
+    assert_eq!(res.json::<Value>().await.unwrap()["code"], "invalid_api_key");
+
+    // Even reading its own keys needs a login
+    let res = client
+        .get("http://localhost:8080/accounts/me/api-keys")
+        .header("Authorization", format!("ApiKey {}", key))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 403);
+    assert_eq!(res.json::<Value>().await.unwrap()["code"], "api_key_not_allowed");
+}
+
+async fn write_api_key_cannot_manage_account(token: Token) {
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://localhost:8080/accounts/me/api-keys")
+        .header("Authorization", format!("Bearer {}", token.access_token))
+        .json(&serde_json::json!({ "name": "bot", "scope": "write" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), 200);
+    let key = res.json::<Value>().await.unwrap()["key"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let requests = vec![
+        client.post("http://localhost:8080/logout?all=true"),
+        client
+            .put("http://localhost:8080/accounts/me")
+            .json(&serde_json::json!({ "display_name": "bot" })),
+        client.delete("http://localhost:8080/accounts/me"),
+    ];
+
+    for request in requests {
+        let res = request
+            .header("Authorization", format!("ApiKey {}", key))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), 403);
+        assert_eq!(res.json::<Value>().await.unwrap()["code"], "api_key_not_allowed");
+    }
+}
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS questions_search_vector_idx;
+DROP TRIGGER IF EXISTS answers_search_vector_trigger ON answers;
+DROP TRIGGER IF EXISTS questions_search_vector_trigger ON questions;
+DROP FUNCTION IF EXISTS answers_refresh_question_search_vector();
+DROP FUNCTION IF EXISTS questions_search_vector_update();
+ALTER TABLE questions
+DROP COLUMN search_vector;
@@ -0,0 +1,44 @@
+-- Add up migration script here
+ALTER TABLE questions
+ADD COLUMN search_vector tsvector;
+
+-- The search vector covers the question itself and the text of all its answers,
+-- weighted so that title matches rank above content and answer matches.
+CREATE OR REPLACE FUNCTION questions_search_vector_update() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector :=
+        setweight(to_tsvector('english', coalesce(NEW.title, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(NEW.content, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(
+            (SELECT string_agg(content, ' ') FROM answers WHERE question_id = NEW.id), ''
+        )), 'C');
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER questions_search_vector_trigger
+BEFORE INSERT OR UPDATE OF title, content ON questions
+FOR EACH ROW EXECUTE FUNCTION questions_search_vector_update();
+
+-- Touching the title of the parent question recomputes its search vector
+-- whenever one of its answers changes.
+CREATE OR REPLACE FUNCTION answers_refresh_question_search_vector() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE questions SET title = title WHERE id = OLD.question_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE questions SET title = title WHERE id = NEW.question_id;
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER answers_search_vector_trigger
+AFTER INSERT OR UPDATE OF content, question_id OR DELETE ON answers
+FOR EACH ROW EXECUTE FUNCTION answers_refresh_question_search_vector();
+
+UPDATE questions SET title = title;
+
+CREATE INDEX IF NOT EXISTS questions_search_vector_idx
+ON questions USING GIN (search_vector);
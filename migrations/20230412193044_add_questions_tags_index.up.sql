@@ -0,0 +1,3 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS questions_tags_idx
+ON questions USING GIN (tags);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS questions_created_on_id_idx
+ON questions (created_on DESC, id DESC);
@@ -0,0 +1,29 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS question_revisions (
+    id serial PRIMARY KEY,
+    question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+    revision integer NOT NULL,
+    title VARCHAR (255) NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT [],
+    account_id integer NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (question_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS answer_revisions (
+    id serial PRIMARY KEY,
+    answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+    revision integer NOT NULL,
+    content TEXT NOT NULL,
+    account_id integer NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (answer_id, revision)
+);
+
+-- The current state of existing posts becomes their first revision
+INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id, created_on)
+SELECT id, 1, title, content, tags, account_id, created_on FROM questions;
+
+INSERT INTO answer_revisions (answer_id, revision, content, account_id, created_on)
+SELECT id, 1, content, account_id, created_on FROM answers;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS email_verified_on TIMESTAMP;
+
+CREATE TABLE IF NOT EXISTS account_tokens (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL,
+    purpose VARCHAR (20) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    token_hash VARCHAR (64) NOT NULL UNIQUE,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP NOT NULL,
+    used_on TIMESTAMP
+);
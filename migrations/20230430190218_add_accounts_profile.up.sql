@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE accounts
+ADD COLUMN IF NOT EXISTS display_name VARCHAR (100),
+ADD COLUMN IF NOT EXISTS bio TEXT,
+ADD COLUMN IF NOT EXISTS avatar_url VARCHAR (2048),
+ADD COLUMN IF NOT EXISTS deleted_on TIMESTAMP;
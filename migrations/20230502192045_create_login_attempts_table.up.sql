@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS login_attempts (
+    key VARCHAR (300) PRIMARY KEY,
+    failures integer NOT NULL DEFAULT 0,
+    last_failure_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP
+);
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS api_keys (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL,
+    name VARCHAR (100) NOT NULL,
+    prefix VARCHAR (16) NOT NULL,
+    key_hash VARCHAR (64) NOT NULL UNIQUE,
+    scope VARCHAR (10) NOT NULL CHECK (scope IN ('read', 'write')),
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP,
+    last_used_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_account_id_idx ON api_keys (account_id);
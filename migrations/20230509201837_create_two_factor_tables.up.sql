@@ -0,0 +1,25 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS two_factor (
+    account_id integer PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    confirmed_on TIMESTAMP,
+    last_used_step BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL,
+    code_hash VARCHAR (64) NOT NULL,
+    used_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_account_id_idx ON recovery_codes (account_id);
+
+CREATE TABLE IF NOT EXISTS login_challenges (
+    challenge_hash VARCHAR (64) PRIMARY KEY,
+    account_id integer NOT NULL,
+    failed_attempts integer NOT NULL DEFAULT 0,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP NOT NULL
+);
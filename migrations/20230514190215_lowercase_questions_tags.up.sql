@@ -0,0 +1,9 @@
+-- Add up migration script here
+UPDATE questions
+SET tags = ARRAY(
+    SELECT lower(tag)
+    FROM unnest(tags) WITH ORDINALITY AS t(tag, position)
+    GROUP BY lower(tag)
+    ORDER BY MIN(position)
+)
+WHERE tags IS NOT NULL AND tags::text <> lower(tags::text);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE votes DROP CONSTRAINT IF EXISTS votes_account_id_fkey;
+ALTER TABLE accounts DROP CONSTRAINT IF EXISTS accounts_id_key;
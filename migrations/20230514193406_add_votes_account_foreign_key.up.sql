@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Accounts are keyed by email, the id needs its own unique constraint to be referenced
+ALTER TABLE accounts ADD CONSTRAINT accounts_id_key UNIQUE (id);
+
+DELETE FROM votes WHERE account_id NOT IN (SELECT id FROM accounts);
+
+ALTER TABLE votes
+ADD CONSTRAINT votes_account_id_fkey
+FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE;
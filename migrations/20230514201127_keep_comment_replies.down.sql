@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE comments
+DROP CONSTRAINT IF EXISTS comments_parent_id_fkey,
+ADD CONSTRAINT comments_parent_id_fkey
+FOREIGN KEY (parent_id) REFERENCES comments ON DELETE CASCADE;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Deleting a comment keeps the replies of other users, they become top level comments
+ALTER TABLE comments
+DROP CONSTRAINT IF EXISTS comments_parent_id_fkey,
+ADD CONSTRAINT comments_parent_id_fkey
+FOREIGN KEY (parent_id) REFERENCES comments ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM login_attempts WHERE LENGTH(key) > 300;
+ALTER TABLE login_attempts ALTER COLUMN key TYPE VARCHAR (300);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Keys hold whole emails, which can be longer than 300 characters
+ALTER TABLE login_attempts ALTER COLUMN key TYPE TEXT;
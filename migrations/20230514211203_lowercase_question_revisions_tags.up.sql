@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- Revisions are rolled back to, so their tags have to be lowercase like the questions' tags
+UPDATE question_revisions
+SET tags = ARRAY(
+    SELECT lower(tag)
+    FROM unnest(tags) WITH ORDINALITY AS t(tag, position)
+    GROUP BY lower(tag)
+    ORDER BY MIN(position)
+)
+WHERE tags IS NOT NULL AND tags::text <> lower(tags::text);
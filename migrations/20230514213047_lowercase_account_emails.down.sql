@@ -0,0 +1,2 @@
+-- Add down migration script here
+-- The original case of the emails isn't kept, there is nothing to restore
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Logins look emails up in lowercase. Accounts whose emails only differ by
+-- case are left as they are, they have to be merged by hand
+UPDATE accounts
+SET email = lower(email)
+WHERE email <> lower(email)
+    AND lower(email) IN (
+        SELECT lower(email) FROM accounts GROUP BY lower(email) HAVING COUNT(*) = 1
+    );
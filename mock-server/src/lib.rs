@@ -1,10 +1,15 @@
 use bytes::Bytes;
-use serde_json::json;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::{oneshot, oneshot::Sender};
 use warp::{http, Filter, Reply};
 
+/// The account the fake OIDC provider signs in when no `login_hint` is given
+pub const OIDC_DEFAULT_EMAIL: &str = "oidc-user@example.com";
+
 #[derive(Clone, Debug)]
 pub struct MockServer {
     socket: SocketAddr,
@@ -54,19 +59,151 @@ impl MockServer {
         }
     }
 
+    /// Describes the fake OIDC provider, every endpoint lives on the mock server
+    async fn oidc_discovery(issuer: String) -> Result<impl warp::Reply, warp::Rejection> {
+        Ok(warp::reply::json(&json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["none"],
+            "code_challenge_methods_supported": ["S256"]
+        })))
+    }
+
+    /// Signs the user in without asking anything and sends the browser back with
+    /// a code. The code carries everything the token endpoint needs to check,
+    /// so the mock doesn't have to keep any state
+    async fn oidc_authorize(
+        params: HashMap<String, String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (redirect_uri, state) = match (params.get("redirect_uri"), params.get("state")) {
+            (Some(redirect_uri), Some(state))
+                if params.get("response_type").map(String::as_str) == Some("code") =>
+            {
+                (redirect_uri, state)
+            }
+            _ => return Ok(oidc_error("invalid_request")),
+        };
+
+        let code = json!({
+            "client_id": params.get("client_id"),
+            "redirect_uri": redirect_uri,
+            "nonce": params.get("nonce"),
+            "code_challenge": params.get("code_challenge"),
+            "email": params
+                .get("login_hint")
+                .map(String::as_str)
+                .unwrap_or(OIDC_DEFAULT_EMAIL),
+        });
+        let query =
+            serde_urlencoded::to_string([("code", encode(&code)), ("state", state.to_string())])
+                .expect("Cannot encode query");
+
+        Ok(warp::http::Response::builder()
+            .status(http::StatusCode::FOUND)
+            .header("Location", format!("{}?{}", redirect_uri, query))
+            .body(String::new())
+            .expect("Cannot build redirect")
+            .into_response())
+    }
+
+    /// Trades a code from `oidc_authorize` for an unsigned ID token
+    async fn oidc_token(
+        issuer: String,
+        form: HashMap<String, String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let code = match form.get("code").and_then(|code| decode(code)) {
+            Some(code)
+                if form.get("grant_type").map(String::as_str) == Some("authorization_code") =>
+            {
+                code
+            }
+            _ => return Ok(oidc_error("invalid_grant")),
+        };
+
+        let verifier = form
+            .get("code_verifier")
+            .map(String::as_str)
+            .unwrap_or_default();
+        let challenge =
+            base64::encode_config(Sha256::digest(verifier.as_bytes()), base64::URL_SAFE_NO_PAD);
+        if code["client_id"].as_str() != form.get("client_id").map(String::as_str)
+            || code["redirect_uri"].as_str() != form.get("redirect_uri").map(String::as_str)
+            || code["code_challenge"].as_str() != Some(challenge.as_str())
+        {
+            return Ok(oidc_error("invalid_grant"));
+        }
+
+        let email = code["email"].as_str().unwrap_or(OIDC_DEFAULT_EMAIL);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Clock is before 1970")
+            .as_secs();
+        let claims = json!({
+            "iss": issuer,
+            "sub": format!("{:x}", Sha256::digest(email.as_bytes())),
+            "aud": code["client_id"],
+            "iat": now,
+            "exp": now + 300,
+            "nonce": code["nonce"],
+            "email": email,
+            "email_verified": true,
+            "name": email.split('@').next(),
+        });
+        let id_token = format!("{}.{}.", encode(&json!({"alg": "none"})), encode(&claims));
+
+        Ok(warp::reply::json(&json!({
+            "access_token": "mock-access-token",
+            "token_type": "Bearer",
+            "expires_in": 300,
+            "id_token": id_token
+        }))
+        .into_response())
+    }
+
     fn build_routes(&self) -> impl Filter<Extract = (impl Reply,)> + Clone {
-        warp::post()
+        let issuer = format!("http://{}", self.socket);
+        let issuer = warp::any().map(move || issuer.clone());
+
+        let bad_words = warp::post()
             .and(warp::path("bad_words"))
             .and(warp::query())
             .map(|_: HashMap<String, String>| ())
             .and(warp::path::end())
             .and(warp::body::bytes())
-            .and_then(Self::check_profanity)
+            .and_then(Self::check_profanity);
+
+        let oidc_discovery = warp::get()
+            .and(warp::path(".well-known"))
+            .and(warp::path("openid-configuration"))
+            .and(warp::path::end())
+            .and(issuer.clone())
+            .and_then(Self::oidc_discovery);
+
+        let oidc_authorize = warp::get()
+            .and(warp::path("authorize"))
+            .and(warp::path::end())
+            .and(warp::query())
+            .and_then(Self::oidc_authorize);
+
+        let oidc_token = warp::post()
+            .and(warp::path("token"))
+            .and(warp::path::end())
+            .and(issuer)
+            .and(warp::body::form())
+            .and_then(Self::oidc_token);
+
+        bad_words
+            .or(oidc_discovery)
+            .or(oidc_authorize)
+            .or(oidc_token)
     }
 
     pub fn oneshot(&self) -> OneshotHandler {
         let (tx, rx) = oneshot::channel::<i32>();
-        let routes = Self::build_routes(&self);
+        let routes = Self::build_routes(self);
 
         let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(self.socket, async {
             rx.await.ok();
@@ -77,3 +214,20 @@ impl MockServer {
         OneshotHandler { sender: tx }
     }
 }
+
+fn encode(value: &Value) -> String {
+    base64::encode_config(value.to_string(), base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(value: &str) -> Option<Value> {
+    let bytes = base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn oidc_error(error: &str) -> warp::reply::Response {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": error })),
+        http::StatusCode::BAD_REQUEST,
+    )
+    .into_response()
+}
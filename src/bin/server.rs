@@ -2,15 +2,20 @@ use rust_question_api::config;
 use rust_question_api::{run, setup_store};
 
 #[tokio::main]
-async fn main() -> Result<(), handle_errors::Error> {
+async fn main() {
     dotenv::dotenv().ok();
 
     let config = config::Config::new().expect("Config can't be set");
-    let store = setup_store(&config).await?;
+    // Logging is only set up along with the store, so failures are printed
+    let store = match setup_store(&config).await {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Cannot start the server: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     tracing::info!("Q&A service build ID {}", env!("RUST_WEB_DEV_VERSION"));
 
     run(config, store).await;
-
-    Ok(())
 }
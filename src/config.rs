@@ -26,6 +26,34 @@ pub struct Config {
     /// Database name
     #[clap(long, default_value = "rustwebdev")]
     pub db_name: String,
+    /// Directory the outgoing mails are written to, they are logged when unset
+    #[clap(long)]
+    pub mail_dir: Option<String>,
+    /// Requests per minute allowed from a client address, 0 turns it off
+    #[clap(long, default_value = "300")]
+    pub rate_limit_ip: u32,
+    /// Requests per minute allowed for an account, 0 turns it off
+    #[clap(long, default_value = "120")]
+    pub rate_limit_account: u32,
+    /// Issuer URL of the OpenID Connect provider, single sign-on is off when unset
+    #[clap(long)]
+    pub oidc_issuer: Option<String>,
+    /// Client id registered with the OpenID Connect provider
+    #[clap(long)]
+    pub oidc_client_id: Option<String>,
+    /// Client secret registered with the OpenID Connect provider
+    #[clap(long)]
+    pub oidc_client_secret: Option<String>,
+    /// Where the provider sends the browser back to, ends in `/auth/oidc/callback`
+    #[clap(long)]
+    pub oidc_redirect_url: Option<String>,
+    /// Key the two-factor secrets are encrypted with, two-factor authentication is off when unset
+    #[clap(long)]
+    pub two_factor_key: Option<String>,
+    /// Accepts the token without the `Bearer` scheme, as older clients send it.
+    /// Deprecated, on by default for this release only
+    #[clap(long, parse(try_from_str), default_value = "true")]
+    pub legacy_auth_header: bool,
 }
 
 impl Config {
@@ -44,7 +72,7 @@ impl Config {
             .ok()
             .map(|val| val.parse::<u16>())
             .unwrap_or(Ok(config.port))
-            .map_err(|e| handle_errors::Error::ParseError(e))?;
+            .map_err(handle_errors::Error::ParseError)?;
 
         let db_user = env::var("POSTGRES_USER").unwrap();
         let db_password = env::var("POSTGRES_PASSWORD").unwrap();
@@ -52,6 +80,29 @@ impl Config {
         let db_port = env::var("POSTGRES_PORT").unwrap();
         let db_name = env::var("POSTGRES_DB").unwrap();
         let log_level = env::var("LOG_LEVEL").unwrap();
+        let mail_dir = env::var("MAIL_DIR").ok().or(config.mail_dir);
+        let rate_limit_ip = env::var("RATE_LIMIT_IP")
+            .ok()
+            .map(|val| val.parse::<u32>())
+            .unwrap_or(Ok(config.rate_limit_ip))
+            .map_err(handle_errors::Error::ParseError)?;
+        let rate_limit_account = env::var("RATE_LIMIT_ACCOUNT")
+            .ok()
+            .map(|val| val.parse::<u32>())
+            .unwrap_or(Ok(config.rate_limit_account))
+            .map_err(handle_errors::Error::ParseError)?;
+        let oidc_issuer = env::var("OIDC_ISSUER").ok().or(config.oidc_issuer);
+        let oidc_client_id = env::var("OIDC_CLIENT_ID").ok().or(config.oidc_client_id);
+        let oidc_client_secret = env::var("OIDC_CLIENT_SECRET")
+            .ok()
+            .or(config.oidc_client_secret);
+        let oidc_redirect_url = env::var("OIDC_REDIRECT_URL")
+            .ok()
+            .or(config.oidc_redirect_url);
+        let two_factor_key = env::var("TWO_FACTOR_KEY").ok().or(config.two_factor_key);
+        let legacy_auth_header = env::var("LEGACY_AUTH_HEADER")
+            .map(|val| val == "true" || val == "1")
+            .unwrap_or(config.legacy_auth_header);
 
         Ok(Config {
             log_level,
@@ -61,8 +112,17 @@ impl Config {
             db_host,
             db_port: db_port
                 .parse::<u16>()
-                .map_err(|e| handle_errors::Error::ParseError(e))?,
+                .map_err(handle_errors::Error::ParseError)?,
             db_name,
+            mail_dir,
+            rate_limit_ip,
+            rate_limit_account,
+            oidc_issuer,
+            oidc_client_id,
+            oidc_client_secret,
+            oidc_redirect_url,
+            two_factor_key,
+            legacy_auth_header,
         })
     }
 }
@@ -86,7 +146,7 @@ mod config_tests {
     #[test]
     fn unset_and_set_api_key() {
         // catch_unwind avoid panicking and captures the error to be tested;
-        let result = catch_unwind(|| Config::new());
+        let result = catch_unwind(Config::new);
         assert!(result.is_err());
 
         set_env();
@@ -98,6 +158,15 @@ mod config_tests {
             db_host: "localhost".to_string(),
             db_port: 5432,
             db_name: "rustwebdev".to_string(),
+            mail_dir: None,
+            rate_limit_ip: 300,
+            rate_limit_account: 120,
+            oidc_issuer: None,
+            oidc_client_id: None,
+            oidc_client_secret: None,
+            oidc_redirect_url: None,
+            two_factor_key: None,
+            legacy_auth_header: true,
         };
 
         let config = Config::new().unwrap();
@@ -16,78 +16,597 @@ pub struct OneshotHandler {
     pub sender: Sender<i32>,
 }
 
-async fn build_routes(store: store::Store) -> impl Filter<Extract = (impl Reply,)> + Clone {
+async fn build_routes(
+    config: &config::Config,
+    store: store::Store,
+) -> impl Filter<Extract = (impl Reply,)> + Clone {
+    let rate_limit = routes::rate_limit::rate_limit(
+        routes::rate_limit::RateLimiter::from_config(config),
+        store.clone(),
+    );
+    let mailer = services::mailer::from_config(config);
+    let mailer_filter = warp::any().map(move || mailer.clone());
+    let oidc = services::oidc::OidcClient::from_config(config);
+    let oidc_filter = warp::any().map(move || oidc.clone());
+    let cipher = services::totp::SecretCipher::from_config(config);
+    let cipher_filter = warp::any().map(move || cipher.clone());
+    let auth = routes::authentication::auth(store.clone(), config.legacy_auth_header);
+    let admin = routes::authentication::auth_with_role(
+        store.clone(),
+        config.legacy_auth_header,
+        types::account::Role::Admin,
+    );
+    // Managing the account's access needs a login, API keys are turned down
+    let logged_in = auth
+        .clone()
+        .and_then(routes::authentication::reject_api_key);
+    let logged_in_admin = admin
+        .clone()
+        .and_then(routes::authentication::reject_api_key);
     let store_filter = warp::any().map(move || store.clone());
 
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_header("content-type")
-        .allow_methods(&[Method::PUT, Method::DELETE, Method::GET, Method::POST]);
+        .allow_headers(vec![
+            "content-type",
+            "authorization",
+            "if-match",
+            "if-none-match",
+            "x-request-id",
+        ])
+        // Browsers only let scripts read the headers listed here
+        .expose_headers(vec![
+            "etag",
+            "x-total-count",
+            "x-request-id",
+            "retry-after",
+            "www-authenticate",
+        ])
+        .allow_methods(&[
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::GET,
+            Method::POST,
+        ]);
 
-    let get_questions = warp::get()
-        .and(warp::path("questions"))
+    let get_questions = warp::path("questions")
         .and(warp::path::end())
+        .and(warp::get())
         .and(warp::query())
         .and(store_filter.clone())
         .and_then(routes::question::get_questions);
 
-    let update_question = warp::put()
-        .and(warp::path("questions"))
+    let get_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(store_filter.clone())
+        .and_then(routes::question::get_question);
+
+    let get_answers = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("answers"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::answer::get_answers);
+
+    let get_tags = warp::path("tags")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::tag::get_tags);
+
+    let suggest_tags = warp::path("tags")
+        .and(warp::path("suggest"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::tag::suggest_tags);
+
+    let update_question = warp::path("questions")
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::put())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(auth.clone())
         .and(store_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::update_question);
 
-    let delete_question = warp::delete()
-        .and(warp::path("questions"))
+    let patch_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::patch())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::merge_patch::merge_patch())
+        .and_then(routes::question::patch_question);
+
+    let delete_question = warp::path("questions")
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::delete())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(auth.clone())
         .and(store_filter.clone())
         .and_then(routes::question::delete_question);
 
-    let add_question = warp::post()
-        .and(warp::path("questions"))
+    let add_question = warp::path("questions")
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::post())
+        .and(auth.clone())
         .and(store_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::add_question);
 
-    let add_answer = warp::post()
-        .and(warp::path("answers"))
+    let accept_answer = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::question::accept_answer);
+
+    let add_answer = warp::path("answers")
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::post())
+        .and(auth.clone())
         .and(store_filter.clone())
         .and(warp::body::form())
         .and_then(routes::answer::add_answer);
 
-    let registration = warp::post()
-        .and(warp::path("registration"))
+    let update_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::answer::update_answer);
+
+    let delete_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::answer::delete_answer);
+
+    let get_question_comments = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::comment::get_question_comments);
+
+    let add_question_comment = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_question_comment);
+
+    let get_answer_comments = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::comment::get_answer_comments);
+
+    let add_answer_comment = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_answer_comment);
+
+    let update_comment = warp::path("comments")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::update_comment);
+
+    let delete_comment = warp::path("comments")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::comment::delete_comment);
+
+    let vote_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_question);
+
+    let withdraw_question_vote = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::vote::withdraw_question_vote);
+
+    let vote_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_answer);
+
+    let withdraw_answer_vote = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::vote::withdraw_answer_vote);
+
+    let get_question_revisions = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::revision::get_question_revisions);
+
+    let diff_question_revisions = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path("diff"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::revision::diff_question_revisions);
+
+    let rollback_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("rollback"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::revision::rollback_question);
+
+    let get_answer_revisions = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::revision::get_answer_revisions);
+
+    let refresh = warp::path("token")
+        .and(warp::path("refresh"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::refresh);
+
+    let logout = warp::path("logout")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::query())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and_then(routes::authentication::logout);
+
+    let get_profile = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::account::get_profile);
+
+    let update_profile = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::update_profile);
+
+    let change_password = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("password"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::change_password);
+
+    let delete_account = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and_then(routes::account::delete_account);
+
+    let get_accounts = warp::path("accounts")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(admin.clone())
+        .and(store_filter.clone())
+        .and_then(routes::account::get_accounts);
+
+    let set_role = warp::path("accounts")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(logged_in_admin.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::set_role);
+
+    let add_api_key = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::api_key::add_api_key);
+
+    let get_api_keys = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and_then(routes::api_key::get_api_keys);
+
+    let delete_api_key = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and_then(routes::api_key::delete_api_key);
+
+    let enroll_two_factor = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("2fa"))
         .and(warp::path::end())
+        .and(warp::post())
+        .and(logged_in.clone())
         .and(store_filter.clone())
+        .and(cipher_filter.clone())
+        .and_then(routes::two_factor::enroll);
+
+    let confirm_two_factor = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("2fa"))
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and(cipher_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::two_factor::confirm);
+
+    let disable_two_factor = warp::path("accounts")
+        .and(warp::path("me"))
+        .and(warp::path("2fa"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(logged_in.clone())
+        .and(store_filter.clone())
+        .and(cipher_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::two_factor::disable);
+
+    let registration = warp::path("registration")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
         .and(warp::body::json())
         .and_then(routes::authentication::register);
 
-    let login = warp::post()
-        .and(warp::path("login"))
+    let verify_email = warp::path("verify-email")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::verify_email);
+
+    let resend_verification = warp::path("verify-email")
+        .and(warp::path("resend"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::resend_verification);
+
+    let forgot_password = warp::path("password")
+        .and(warp::path("forgot"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::forgot_password);
+
+    let reset_password = warp::path("password")
+        .and(warp::path("reset"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::reset_password);
+
+    let login = warp::path("login")
         .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::addr::remote())
         .and(store_filter.clone())
         .and(warp::body::json())
         .and_then(routes::authentication::login);
 
-    get_questions
+    let verify_login = warp::path("login")
+        .and(warp::path("2fa"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(cipher_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::two_factor::verify_login);
+
+    let oidc_start = warp::path("auth")
+        .and(warp::path("oidc"))
+        .and(warp::path("start"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and(oidc_filter.clone())
+        .and_then(routes::oidc::oidc_start);
+
+    let oidc_callback = warp::path("auth")
+        .and(warp::path("oidc"))
+        .and(warp::path("callback"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and(oidc_filter.clone())
+        .and_then(routes::oidc::oidc_callback);
+
+    // Each group is boxed, otherwise the type of the whole filter chain
+    // gets deep enough to overflow the stack of debug builds
+    let question_routes = get_questions
+        .or(get_question)
         .or(update_question)
+        .or(patch_question)
         .or(add_question)
         .or(delete_question)
+        .or(accept_answer)
+        .or(get_question_revisions)
+        .or(diff_question_revisions)
+        .or(rollback_question)
+        .boxed();
+
+    let answer_routes = get_answers
         .or(add_answer)
-        .or(registration)
+        .or(update_answer)
+        .or(delete_answer)
+        .or(get_answer_revisions)
+        .boxed();
+
+    let tag_routes = get_tags.or(suggest_tags).boxed();
+
+    let comment_routes = get_question_comments
+        .or(add_question_comment)
+        .or(get_answer_comments)
+        .or(add_answer_comment)
+        .or(update_comment)
+        .or(delete_comment)
+        .boxed();
+
+    let vote_routes = vote_question
+        .or(withdraw_question_vote)
+        .or(vote_answer)
+        .or(withdraw_answer_vote)
+        .boxed();
+
+    let account_routes = get_profile
+        .or(update_profile)
+        .or(change_password)
+        .or(delete_account)
+        .or(get_accounts)
+        .or(set_role)
+        .or(add_api_key)
+        .or(get_api_keys)
+        .or(delete_api_key)
+        .or(enroll_two_factor)
+        .or(confirm_two_factor)
+        .or(disable_two_factor)
+        .boxed();
+
+    let authentication_routes = registration
+        .or(verify_email)
+        .or(resend_verification)
+        .or(forgot_password)
+        .or(reset_password)
         .or(login)
-        .with(cors)
-        .with(warp::trace::request())
-        .recover(handle_errors::return_error)
+        .or(verify_login)
+        .or(refresh)
+        .or(logout)
+        .or(oidc_start)
+        .or(oidc_callback)
+        .boxed();
+
+    let routes = rate_limit
+        .and(
+            question_routes
+                .or(answer_routes)
+                .or(tag_routes)
+                .or(comment_routes)
+                .or(vote_routes)
+                .or(account_routes)
+                .or(authentication_routes),
+        )
+        .with(cors);
+
+    // Rejections are turned into replies along with the request id,
+    // which is why they are caught here instead of with `recover`
+    routes::request_id::request_id()
+        .and(
+            routes
+                .map(|reply| Ok::<_, warp::Rejection>(Reply::into_response(reply)))
+                .or_else(|rejection| async move {
+                    Ok::<_, std::convert::Infallible>((Err(rejection),))
+                }),
+        )
+        .and_then(routes::request_id::reply_with_request_id)
+        .with(routes::request_id::trace_request())
 }
 
 pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle_errors::Error> {
@@ -103,9 +622,17 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
         .await
         .map_err(handle_errors::Error::MigrationError)?;
 
+    // Without the key these accounts would get a login challenge they can't pass
+    if config.two_factor_key.is_none() && store.has_two_factor().await? {
+        return Err(handle_errors::Error::ConfigError(
+            "TWO_FACTOR_KEY not set, but accounts have two-factor authentication turned on"
+                .to_string(),
+        ));
+    }
+
     let log_filter = format!(
-        "handle_errors={},rust_web_dev={},warp={}",
-        config.log_level, config.log_level, config.log_level
+        "handle_errors={},rust_web_dev={},rust_question_api={},warp={}",
+        config.log_level, config.log_level, config.log_level, config.log_level
     );
 
     tracing_subscriber::fmt()
@@ -120,12 +647,12 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
 }
 
 pub async fn run(config: config::Config, store: store::Store) {
-    let routes = build_routes(store).await;
+    let routes = build_routes(&config, store).await;
     warp::serve(routes).run(([0, 0, 0, 0], config.port)).await;
 }
 
-pub async fn oneshot(store: store::Store) -> OneshotHandler {
-    let routes = build_routes(store).await;
+pub async fn oneshot(config: &config::Config, store: store::Store) -> OneshotHandler {
+    let routes = build_routes(config, store).await;
     let (tx, rx) = oneshot::channel::<i32>();
 
     let socket: std::net::SocketAddr = "127.0.0.1:8080"
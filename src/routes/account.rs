@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use handle_errors::Error;
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::{
+    routes::authentication::{hash_password, verify_password},
+    store::Store,
+    types::{
+        account::{AccountRole, ChangePassword, Role, Session, UpdateProfile},
+        pagination::extract_list_query,
+        validation::Validate,
+    },
+};
+
+pub async fn get_profile(session: Session, store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_profile(&session.account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_profile(
+    session: Session,
+    store: Store,
+    profile: UpdateProfile,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    profile.validate()?;
+    match store.update_profile(&session.account_id, profile).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Changes the password after checking the current one, the other
+/// sessions of the account get logged out
+pub async fn change_password(
+    session: Session,
+    store: Store,
+    change_password: ChangePassword,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    change_password.validate()?;
+    let account = store.get_account_by_id(&session.account_id).await?;
+    // Accounts created through single sign-on set their first password with a reset
+    if account.password.is_empty() {
+        return Err(warp::reject::custom(Error::WrongPassword));
+    }
+
+    match verify_password(&account.password, change_password.old_password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => return Err(warp::reject::custom(Error::WrongPassword)),
+        Err(e) => return Err(warp::reject::custom(Error::ArgonLibraryError(e))),
+    }
+
+    let hashed_password = hash_password(change_password.new_password.as_bytes());
+    match store
+        .update_password(&session.account_id, hashed_password, &session.jti)
+        .await
+    {
+        Ok(_) => Ok(warp::reply::with_status("Password updated", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Closes the account, its posts are kept but no longer tied to a person
+pub async fn delete_account(
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.anonymize_account(&session.account_id).await {
+        Ok(_) => {
+            event!(
+                Level::INFO,
+                account_id = session.account_id.0,
+                "account deleted"
+            );
+            Ok(warp::reply::with_status("Account deleted", StatusCode::OK))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Lists the accounts for admins, optionally only the ones holding `role`
+/// `/accounts?role=moderator&limit=10&offset=0`
+pub async fn get_accounts(
+    params: HashMap<String, String>,
+    _session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let list = extract_list_query(&params)?;
+    list.ensure_offset_only()?;
+    let role = params.get("role").map(|role| Role::parse(role)).transpose()?;
+
+    match store.get_accounts(role, list.limit, list.offset).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Changes the role of an account, its sessions are revoked so the
+/// new role applies from its next login
+pub async fn set_role(
+    id: i32,
+    session: Session,
+    store: Store,
+    account_role: AccountRole,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.set_role(id, account_role.role).await?;
+    store.revoke_all_sessions(&account.id).await?;
+
+    event!(
+        Level::INFO,
+        account_id = id,
+        role = account.role.as_str(),
+        admin_id = session.account_id.0,
+        "role changed"
+    );
+
+    Ok(warp::reply::json(&account))
+}
@@ -1,16 +1,41 @@
+use std::collections::HashMap;
+
+use handle_errors::Error;
+use tracing::{event, instrument, Level};
 use warp::http::StatusCode;
 
 use crate::services::profanity::check_profanity;
 use crate::store::Store;
 use crate::types::account::Session;
-use crate::types::answer::NewAnswer;
+use crate::types::answer::{NewAnswer, UpdateAnswer};
+use crate::types::pagination::extract_list_query;
+use crate::types::validation::Validate;
+
+#[instrument]
+pub async fn get_answers(
+    question_id: i32,
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, question_id, "querying answers");
+    let list = extract_list_query(&params)?;
+    list.ensure_offset_only()?;
+
+    match store
+        .get_answers(question_id, list.limit, list.offset)
+        .await
+    {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
 
 pub async fn add_answer(
     session: Session,
     store: Store,
     new_answer: NewAnswer,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-
+    new_answer.validate()?;
     let account_id = session.account_id;
 
     let content = match 
@@ -29,3 +54,55 @@ pub async fn add_answer(
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
+
+pub async fn update_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    answer: UpdateAnswer,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    answer.validate()?;
+    let is_moderator = session.is_moderator();
+    let account_id = session.account_id;
+
+    if is_moderator || store.is_answer_owner(id, &account_id).await? {
+        let content = match check_profanity(answer.content).await {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+
+        match store
+            .update_answer(UpdateAnswer { content }, id, account_id)
+            .await
+        {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+pub async fn delete_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let is_moderator = session.is_moderator();
+    let account_id = session.account_id;
+    if is_moderator || store.is_answer_owner(id, &account_id).await? {
+        match store.delete_answer(id).await {
+            Ok(true) => Ok(warp::reply::with_status(
+                format!("Answer {} deleted", id),
+                StatusCode::OK,
+            )),
+            Ok(false) => Err(warp::reject::custom(Error::NotFound(format!(
+                "answer {}",
+                id
+            )))),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
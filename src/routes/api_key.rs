@@ -0,0 +1,77 @@
+use handle_errors::Error;
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::{
+    routes::authentication::{generate_token, hash_token},
+    store::Store,
+    types::{
+        account::Session,
+        api_key::{CreatedApiKey, NewApiKey},
+        validation::Validate,
+    },
+};
+
+/// Keys start with this, so they are easy to spot in a leaked config
+const API_KEY_PREFIX: &str = "qa_";
+/// Length of the start of the key kept in clear to tell the keys apart
+const API_KEY_PREFIX_LEN: usize = 10;
+
+/// Creates a key for the account, the only reply that ever contains it
+pub async fn add_api_key(
+    session: Session,
+    store: Store,
+    new_api_key: NewApiKey,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    new_api_key.validate()?;
+    let key = format!("{}{}", API_KEY_PREFIX, generate_token());
+
+    match store
+        .add_api_key(
+            &session.account_id,
+            new_api_key,
+            &key[..API_KEY_PREFIX_LEN],
+            &hash_token(&key),
+        )
+        .await
+    {
+        Ok(api_key) => {
+            event!(
+                Level::INFO,
+                account_id = session.account_id.0,
+                api_key_id = api_key.id.0,
+                "API key created"
+            );
+            Ok(warp::reply::json(&CreatedApiKey { api_key, key }))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_api_keys(
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_api_keys(&session.account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_api_key(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.delete_api_key(id, &session.account_id).await {
+        Ok(true) => Ok(warp::reply::with_status(
+            format!("API key {} deleted", id),
+            StatusCode::OK,
+        )),
+        Ok(false) => Err(warp::reject::custom(Error::NotFound(format!(
+            "API key {}",
+            id
+        )))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,121 @@
+use handle_errors::Error;
+use tracing::{event, instrument, Level};
+use warp::http::StatusCode;
+
+use crate::services::profanity::check_profanity;
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::comment::{build_threads, CommentTarget, NewComment, UpdateComment};
+use crate::types::validation::Validate;
+
+#[instrument]
+pub async fn get_question_comments(
+    id: i32,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, question_id = id, "querying comments");
+    match store.get_comments(CommentTarget::Question(id)).await {
+        Ok(res) => Ok(warp::reply::json(&build_threads(res))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+#[instrument]
+pub async fn get_answer_comments(
+    id: i32,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, answer_id = id, "querying comments");
+    match store.get_comments(CommentTarget::Answer(id)).await {
+        Ok(res) => Ok(warp::reply::json(&build_threads(res))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn add_question_comment(
+    id: i32,
+    session: Session,
+    store: Store,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    add_comment(CommentTarget::Question(id), session, store, new_comment).await
+}
+
+pub async fn add_answer_comment(
+    id: i32,
+    session: Session,
+    store: Store,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    add_comment(CommentTarget::Answer(id), session, store, new_comment).await
+}
+
+async fn add_comment(
+    target: CommentTarget,
+    session: Session,
+    store: Store,
+    new_comment: NewComment,
+) -> Result<warp::reply::Json, warp::Rejection> {
+    new_comment.validate()?;
+    let content = match check_profanity(new_comment.content).await {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let comment = NewComment {
+        content,
+        parent_id: new_comment.parent_id,
+    };
+
+    match store.add_comment(target, comment, session.account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_comment(
+    id: i32,
+    session: Session,
+    store: Store,
+    comment: UpdateComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    comment.validate()?;
+    let account_id = session.account_id;
+
+    if store.is_comment_owner(id, &account_id).await? {
+        let content = match check_profanity(comment.content).await {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+
+        match store.update_comment(content, id, account_id).await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+pub async fn delete_comment(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_comment_owner(id, &account_id).await? {
+        match store.delete_comment(id).await {
+            Ok(true) => Ok(warp::reply::with_status(
+                format!("Comment {} deleted", id),
+                StatusCode::OK,
+            )),
+            Ok(false) => Err(warp::reject::custom(Error::NotFound(format!(
+                "comment {}",
+                id
+            )))),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
@@ -0,0 +1,51 @@
+use handle_errors::Error;
+use serde::de::DeserializeOwned;
+use warp::{hyper::body::Bytes, Filter, Rejection};
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Reads a JSON Merge Patch (RFC 7396) body, which `warp::body::json`
+/// turns down for its content type. Plain JSON is taken as well.
+pub fn merge_patch<T>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+where
+    T: DeserializeOwned + Send,
+{
+    warp::header::optional::<String>("content-type")
+        .and_then(|content_type: Option<String>| async move {
+            match content_type {
+                Some(content_type) if !is_merge_patch_content_type(&content_type) => Err(
+                    warp::reject::custom(Error::UnsupportedMediaType(content_type)),
+                ),
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+        .and(warp::body::bytes())
+        .and_then(|body: Bytes| async move {
+            serde_json::from_slice::<T>(&body)
+                .map_err(|e| warp::reject::custom(Error::InvalidBody(e.to_string())))
+        })
+}
+
+fn is_merge_patch_content_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    media_type.eq_ignore_ascii_case(MERGE_PATCH_CONTENT_TYPE)
+        || media_type.eq_ignore_ascii_case("application/json")
+}
+
+#[cfg(test)]
+mod merge_patch_tests {
+    use super::is_merge_patch_content_type;
+
+    #[test]
+    fn merge_patch_content_types() {
+        assert!(is_merge_patch_content_type("application/merge-patch+json"));
+        assert!(is_merge_patch_content_type(
+            "Application/JSON; charset=utf-8"
+        ));
+        assert!(!is_merge_patch_content_type(
+            "application/x-www-form-urlencoded"
+        ));
+        assert!(!is_merge_patch_content_type("application/json-patch+json"));
+    }
+}
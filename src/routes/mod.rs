@@ -1,3 +1,14 @@
+pub mod account;
 pub mod answer;
+pub mod api_key;
+pub mod comment;
+pub mod merge_patch;
+pub mod oidc;
 pub mod question;
-pub mod authentication;
\ No newline at end of file
+pub mod rate_limit;
+pub mod request_id;
+pub mod revision;
+pub mod authentication;
+pub mod tag;
+pub mod two_factor;
+pub mod vote;
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::{collections::HashMap, sync::Arc};
+
+use handle_errors::Error;
+use tracing::{event, Level};
+use warp::http::Uri;
+
+use crate::{
+    routes::authentication::{generate_token, hash_token, issue_login_challenge, start_session},
+    services::oidc::OidcClient,
+    store::Store,
+    types::account::{normalize_email, ExternalIdentity},
+};
+
+/// Time the user gets to sign in at the provider
+const OIDC_LOGIN_MINUTES: i32 = 10;
+
+/// Sends the browser to the provider, `login_hint` is passed along to prefill the form
+pub async fn oidc_start(
+    params: HashMap<String, String>,
+    store: Store,
+    oidc: Option<Arc<OidcClient>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let oidc = oidc.ok_or(Error::OidcNotConfigured)?;
+
+    let state = generate_token();
+    let nonce = generate_token();
+    let code_verifier = generate_token();
+
+    store
+        .add_oidc_login(
+            &hash_token(&state),
+            &nonce,
+            &code_verifier,
+            OIDC_LOGIN_MINUTES,
+        )
+        .await?;
+
+    let url = oidc
+        .authorization_url(
+            &state,
+            &nonce,
+            &code_verifier,
+            params.get("login_hint").map(String::as_str),
+        )
+        .await?;
+
+    match url.parse::<Uri>() {
+        Ok(uri) => Ok(warp::redirect::see_other(uri)),
+        Err(_) => Err(warp::reject::custom(Error::OidcLoginFailed(
+            "authorization endpoint is not a valid URL".to_string(),
+        ))),
+    }
+}
+
+/// Where the provider sends the browser back to. Signs in the account linked
+/// to the identity, linking or creating one on the first visit
+pub async fn oidc_callback(
+    params: HashMap<String, String>,
+    store: Store,
+    oidc: Option<Arc<OidcClient>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let oidc = oidc.ok_or(Error::OidcNotConfigured)?;
+
+    if let Some(error) = params.get("error") {
+        return Err(warp::reject::custom(Error::OidcLoginFailed(format!(
+            "provider replied with `{}`",
+            error
+        ))));
+    }
+
+    let (code, state) = match (params.get("code"), params.get("state")) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(warp::reject::custom(Error::MissingParameters)),
+    };
+
+    let (nonce, code_verifier) = store
+        .take_oidc_login(&hash_token(state))
+        .await?
+        .ok_or_else(|| Error::OidcLoginFailed("login is unknown or has expired".to_string()))?;
+
+    let claims = oidc.exchange_code(code, &code_verifier, &nonce).await?;
+    let email = claims
+        .email
+        .ok_or_else(|| Error::OidcLoginFailed("provider did not share an email".to_string()))?;
+
+    let identity = ExternalIdentity {
+        issuer: claims.iss,
+        subject: claims.sub,
+        email: normalize_email(&email),
+        email_verified: claims.email_verified,
+        display_name: claims.name,
+    };
+
+    match store.sign_in_with_identity(identity).await? {
+        Some((account_id, role)) => {
+            event!(
+                Level::INFO,
+                account_id = account_id.0,
+                "signed in through single sign-on"
+            );
+            if let Some(challenge) = issue_login_challenge(&store, &account_id).await? {
+                return Ok(warp::reply::json(&challenge));
+            }
+
+            let tokens = start_session(&store, account_id, role).await?;
+            Ok(warp::reply::json(&tokens))
+        }
+        None => Err(warp::reject::custom(Error::OidcLoginFailed(
+            "the email belongs to an account and was not verified by the provider".to_string(),
+        ))),
+    }
+}
@@ -4,15 +4,26 @@ use std::collections::HashMap;
 
 use handle_errors::Error;
 use tracing::{event, instrument, Level};
-use warp::hyper::StatusCode;
+use warp::{
+    http::header::{CONTENT_TYPE, ETAG},
+    hyper::StatusCode,
+    reply::Response,
+    Reply,
+};
 
 use crate::{
     services::profanity::check_profanity,
     store::Store,
     types::{
         account::Session,
-        pagination::{extract_pagination, Pagination},
-        question::{NewQuestion, Question},
+        etag::{extract_if_match, none_match, reply_etag, IfMatch},
+        pagination::extract_list_query,
+        question::{
+            extract_question_filter, NewQuestion, Question, QuestionPatch, QuestionWithAnswers,
+            UpdateQuestion,
+        },
+        tag::normalize_tags,
+        validation::Validate,
     },
 };
 
@@ -22,27 +33,121 @@ pub async fn get_questions(
     store: Store,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(target: "practical_rust_book", Level::INFO, "querying questions");
-    let mut pagination = Pagination::default();
+    let list = extract_list_query(&params)?;
+    let filter = extract_question_filter(&params)?;
 
-    if !params.is_empty() {
-        event!(Level::INFO, pagination = true);
-        pagination = extract_pagination(params)?;
-    }
+    let page = match params.get("q") {
+        Some(query) => {
+            event!(Level::INFO, search = query.as_str());
+            if list.cursor.is_some() {
+                return Err(warp::reject::custom(Error::InvalidParameter(
+                    "search results are paged with offset, not cursor".to_string(),
+                )));
+            }
+
+            store
+                .search_questions(query, &list, &filter)
+                .await
+                .map(|page| (page.total, warp::reply::json(&page)))
+        }
+        None => store
+            .get_questions(&list, &filter)
+            .await
+            .map(|page| (page.total, warp::reply::json(&page))),
+    };
 
-    match store
-        .get_questions(pagination.limit, pagination.offset)
-        .await
-    {
-        Ok(res) => Ok(warp::reply::json(&res)),
+    match page {
+        Ok((total, res)) => Ok(warp::reply::with_header(
+            res,
+            "X-Total-Count",
+            total.to_string(),
+        )),
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
+/// Answers with `304 Not Modified` when the client already has the current
+/// reply, named in `If-None-Match`. The tag changes with the answers and
+/// votes as well, not only with the version of the question.
+#[instrument]
+pub async fn get_question(
+    id: i32,
+    if_none_match: Option<String>,
+    store: Store,
+) -> Result<Response, warp::Rejection> {
+    event!(Level::INFO, question_id = id, "querying question");
+    let reply = question_reply(&store, id).await?;
+
+    if if_none_match.is_some_and(|header| none_match(&header, &reply.tag)) {
+        let res = warp::reply::with_status(warp::reply(), StatusCode::NOT_MODIFIED);
+        return Ok(warp::reply::with_header(res, ETAG, reply.tag).into_response());
+    }
+
+    let res = warp::reply::with_header(reply.body, CONTENT_TYPE, "application/json");
+    Ok(warp::reply::with_header(res, ETAG, reply.tag).into_response())
+}
+
+/// The question with its answers as `GET /questions/{id}` replies it
+struct QuestionReply {
+    version: i32,
+    body: Vec<u8>,
+    tag: String,
+}
+
+async fn question_reply(store: &Store, id: i32) -> Result<QuestionReply, Error> {
+    let question = store.get_question(id).await?;
+    let answers = store.get_answers(id, None, 0).await?;
+    let version = question.version;
+    let body = serde_json::to_vec(&QuestionWithAnswers { question, answers })
+        .expect("question serializes to JSON");
+    let tag = reply_etag(version, &body);
+
+    Ok(QuestionReply { version, body, tag })
+}
+
+/// The version `If-Match` lets a change be made on, `None` for `*`.
+/// The header has to name the tag the question has right now.
+pub(crate) async fn if_match_version(
+    store: &Store,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<Option<i32>, Error> {
+    if *if_match == IfMatch::Any {
+        return Ok(None);
+    }
+
+    let current = question_reply(store, id).await?;
+    if if_match.matches(&current.tag) {
+        Ok(Some(current.version))
+    } else {
+        Err(Error::PreconditionFailed)
+    }
+}
+
+/// Replies the changed question along with the tag it has now, the one the
+/// next change has to send in `If-Match`. The tag is left out when the
+/// question was changed again in the meantime.
+pub(crate) async fn changed_question_reply(
+    store: &Store,
+    question: Question,
+) -> Result<Response, Error> {
+    let current = question_reply(store, question.id.0).await?;
+    let res = warp::reply::json(&question).into_response();
+
+    if current.version == question.version {
+        Ok(warp::reply::with_header(res, ETAG, current.tag).into_response())
+    } else {
+        Ok(res)
+    }
+}
+
 pub async fn add_question(
     session: Session,
     store: Store,
-    new_question: NewQuestion,
+    mut new_question: NewQuestion,
 ) -> Result<impl warp::Reply, warp::Rejection> {
+    new_question.tags = new_question.tags.map(normalize_tags);
+    new_question.validate()?;
     let account_id = session.account_id;
 
     let title = check_profanity(new_question.title);
@@ -50,17 +155,19 @@ pub async fn add_question(
 
     let (title, content) = tokio::join!(title, content);
 
-    if title.is_err() {
-        return Err(warp::reject::custom(title.unwrap_err()));
-    }
+    let title = match title {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
 
-    if content.is_err() {
-        return Err(warp::reject::custom(content.unwrap_err()));
-    }
+    let content = match content {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
 
     let question = NewQuestion {
-        title: title.unwrap(),
-        content: content.unwrap(),
+        title,
+        content,
         tags: new_question.tags,
     };
 
@@ -73,34 +180,115 @@ pub async fn add_question(
 // pay attention!!! the signature need to follow this order!!! param, store, item to be updated
 pub async fn update_question(
     id: i32,
+    if_match: Option<String>,
     session: Session,
     store: Store,
-    question: Question,
-) -> Result<impl warp::Reply, warp::Rejection> {
+    mut question: UpdateQuestion,
+) -> Result<Response, warp::Rejection> {
+    let if_match = extract_if_match(if_match.as_deref())?;
+    question.tags = question.tags.map(normalize_tags);
+    question.validate()?;
+    let is_moderator = session.is_moderator();
     let account_id = session.account_id;
 
-    if store.is_question_owner(id, &account_id).await? {
+    if is_moderator || store.is_question_owner(id, &account_id).await? {
         let title = check_profanity(question.title);
         let content = check_profanity(question.content);
 
         let (title, content) = tokio::join!(title, content);
 
-        if title.is_err() {
-            return Err(warp::reject::custom(title.unwrap_err()));
+        let title = match title {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+
+        let content = match content {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+
+        let question = UpdateQuestion {
+            title,
+            content,
+            tags: question.tags,
+        };
+
+        let version = if_match_version(&store, id, &if_match).await?;
+        match store
+            .update_question(question, id, account_id, version)
+            .await
+        {
+            Ok(res) => Ok(changed_question_reply(&store, res).await?),
+            Err(e) => Err(warp::reject::custom(e)),
         }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
 
-        if content.is_err() {
-            return Err(warp::reject::custom(content.unwrap_err()));
+/// Applies a JSON Merge Patch to the question, the profanity filter only
+/// runs on the texts the patch changes
+pub async fn patch_question(
+    id: i32,
+    if_match: Option<String>,
+    session: Session,
+    store: Store,
+    mut patch: QuestionPatch,
+) -> Result<Response, warp::Rejection> {
+    let if_match = extract_if_match(if_match.as_deref())?;
+    patch.tags = patch.tags.map(|tags| tags.map(normalize_tags));
+    patch.validate()?;
+    let is_moderator = session.is_moderator();
+    let account_id = session.account_id;
+
+    if is_moderator || store.is_question_owner(id, &account_id).await? {
+        let version = if_match_version(&store, id, &if_match).await?;
+        let current = store.get_question(id).await?;
+        if version.is_some_and(|version| version != current.version) {
+            return Err(warp::reject::custom(Error::PreconditionFailed));
         }
 
-        let question = Question {
-            id: question.id,
-            title: title.unwrap(),
-            content: content.unwrap(),
+        let question = patch.apply(&current);
+        let (title, content) = tokio::join!(
+            check_profanity_if_changed(question.title, &current.title),
+            check_profanity_if_changed(question.content, &current.content),
+        );
+
+        let question = UpdateQuestion {
+            title: title?,
+            content: content?,
             tags: question.tags,
         };
 
-        match store.update_question(question, id, account_id).await {
+        // The patch was merged into this version, so only write over that one
+        match store
+            .update_question(question, id, account_id, Some(current.version))
+            .await
+        {
+            Ok(res) => Ok(changed_question_reply(&store, res).await?),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+async fn check_profanity_if_changed(text: String, current: &str) -> Result<String, Error> {
+    if text == current {
+        Ok(text)
+    } else {
+        check_profanity(text).await
+    }
+}
+
+pub async fn accept_answer(
+    id: i32,
+    answer_id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if store.is_question_owner(id, &session.account_id).await? {
+        match store.accept_answer(id, answer_id).await {
             Ok(res) => Ok(warp::reply::json(&res)),
             Err(e) => Err(warp::reject::custom(e)),
         }
@@ -111,16 +299,24 @@ pub async fn update_question(
 
 pub async fn delete_question(
     id: i32,
+    if_match: Option<String>,
     session: Session,
     store: Store,
 ) -> Result<impl warp::Reply, warp::Rejection> {
+    let if_match = extract_if_match(if_match.as_deref())?;
+    let is_moderator = session.is_moderator();
     let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        match store.delete_question(id).await {
-            Ok(_) => Ok(warp::reply::with_status(
+    if is_moderator || store.is_question_owner(id, &account_id).await? {
+        let version = if_match_version(&store, id, &if_match).await?;
+        match store.delete_question(id, version).await {
+            Ok(true) => Ok(warp::reply::with_status(
                 format!("Question {} deleted", id),
                 StatusCode::OK,
             )),
+            Ok(false) => Err(warp::reject::custom(Error::NotFound(format!(
+                "question {}",
+                id
+            )))),
             Err(e) => Err(warp::reject::custom(e)),
         }
     } else {
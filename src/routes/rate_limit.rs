@@ -0,0 +1,239 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use handle_errors::Error;
+use tracing::{event, Level};
+use warp::Filter;
+
+use crate::{
+    config::Config,
+    routes::authentication::{hash_token, verify_token, Credentials},
+    store::Store,
+    types::account::AccountId,
+};
+
+/// Number of buckets kept, the least recently used one is dropped for a new client
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Account(AccountId),
+}
+
+/// Allowed requests per minute, where `0` turns the limit off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit(pub u32);
+
+impl Limit {
+    fn refill_per_second(&self) -> f64 {
+        self.0 as f64 / 60.0
+    }
+}
+
+/// Holds up to a minute worth of requests, refilling continuously
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated_on: Instant,
+}
+
+impl Bucket {
+    fn new(limit: Limit, now: Instant) -> Bucket {
+        Bucket {
+            tokens: limit.0 as f64,
+            updated_on: now,
+        }
+    }
+
+    /// Takes a token for a request, or tells how many seconds until the next one
+    fn take(&mut self, limit: Limit, now: Instant) -> Result<(), u64> {
+        let elapsed = now.saturating_duration_since(self.updated_on).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.refill_per_second()).min(limit.0 as f64);
+        self.updated_on = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - self.tokens) / limit.refill_per_second()).ceil() as u64)
+        }
+    }
+}
+
+/// Buckets with the order they were last used in, so the least recently
+/// used one is found without going through all of them
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<Client, (Bucket, u64)>,
+    by_use: BTreeMap<u64, Client>,
+    uses: u64,
+}
+
+impl Buckets {
+    fn get_mut(&mut self, client: &Client, limit: Limit, now: Instant) -> &mut Bucket {
+        self.uses += 1;
+        let used = self.uses;
+
+        if let Some((_, last_used)) = self.buckets.get(client) {
+            self.by_use.remove(last_used);
+        } else if self.buckets.len() >= MAX_BUCKETS {
+            if let Some((_, oldest)) = self.by_use.pop_first() {
+                self.buckets.remove(&oldest);
+            }
+        }
+        self.by_use.insert(used, client.clone());
+
+        let (bucket, last_used) = self
+            .buckets
+            .entry(client.clone())
+            .or_insert_with(|| (Bucket::new(limit, now), used));
+        *last_used = used;
+        bucket
+    }
+}
+
+/// Token buckets per client address and per account, shared by all routes
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    ip_limit: Limit,
+    account_limit: Limit,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(ip_limit: Limit, account_limit: Limit) -> RateLimiter {
+        RateLimiter {
+            ip_limit,
+            account_limit,
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> RateLimiter {
+        RateLimiter::new(
+            Limit(config.rate_limit_ip),
+            Limit(config.rate_limit_account),
+        )
+    }
+
+    fn check(&self, client: Client, now: Instant) -> Result<(), Error> {
+        let limit = match client {
+            Client::Ip(_) => self.ip_limit,
+            Client::Account(_) => self.account_limit,
+        };
+        if limit.0 == 0 {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        buckets
+            .get_mut(&client, limit, now)
+            .take(limit, now)
+            .map_err(|retry_after| {
+                event!(Level::WARN, client = ?client, retry_after, "rate limited");
+                Error::RateLimited(retry_after)
+            })
+    }
+}
+
+/// Throttles every request by its client address and, when it carries a
+/// valid token or API key, by its account as well. All keys of an account
+/// share its bucket, so creating more keys doesn't raise the limit
+pub fn rate_limit(
+    limiter: RateLimiter,
+    store: Store,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("Authorization"))
+        .and_then(move |addr: Option<SocketAddr>, header: Option<String>| {
+            let limiter = limiter.clone();
+            let store = store.clone();
+            async move {
+                let now = Instant::now();
+                if let Some(addr) = addr {
+                    limiter.check(Client::Ip(addr.ip()), now)?;
+                }
+                let account_id = match header.map(|header| Credentials::parse(&header, true)) {
+                    Some(Ok(Credentials::Bearer(token))) => {
+                        verify_token(token).ok().map(|session| session.account_id)
+                    }
+                    Some(Ok(Credentials::ApiKey(key))) => {
+                        store.api_key_account(&hash_token(&key)).await?
+                    }
+                    _ => None,
+                };
+                if let Some(account_id) = account_id {
+                    limiter.check(Client::Account(account_id), now)?;
+                }
+                Ok::<(), warp::Rejection>(())
+            }
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use super::{AccountId, Client, Instant, Limit, RateLimiter, MAX_BUCKETS};
+    use std::time::Duration;
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = RateLimiter::new(Limit(2), Limit(0));
+        let client = Client::Ip("127.0.0.1".parse().unwrap());
+        let now = Instant::now();
+
+        assert!(limiter.check(client.clone(), now).is_ok());
+        assert!(limiter.check(client.clone(), now).is_ok());
+        let limited = format!("{}", limiter.check(client.clone(), now).unwrap_err());
+        assert_eq!(limited, "Too many requests, retry in 30 seconds");
+
+        assert!(limiter.check(client, now + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn clients_have_their_own_buckets() {
+        let limiter = RateLimiter::new(Limit(1), Limit(1));
+        let now = Instant::now();
+
+        assert!(limiter.check(Client::Account(AccountId(1)), now).is_ok());
+        assert!(limiter.check(Client::Account(AccountId(1)), now).is_err());
+        assert!(limiter.check(Client::Account(AccountId(2)), now).is_ok());
+        assert!(limiter
+            .check(Client::Ip("127.0.0.1".parse().unwrap()), now)
+            .is_ok());
+    }
+
+    #[test]
+    fn least_recently_used_bucket_is_dropped() {
+        let limiter = RateLimiter::new(Limit(0), Limit(1));
+        let now = Instant::now();
+
+        for id in 0..MAX_BUCKETS as i32 {
+            assert!(limiter.check(Client::Account(AccountId(id)), now).is_ok());
+        }
+        assert!(limiter.check(Client::Account(AccountId(0)), now).is_err());
+
+        // Account 1 is now the least recently used one and makes room
+        assert!(limiter
+            .check(Client::Account(AccountId(MAX_BUCKETS as i32)), now)
+            .is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().buckets.len(), MAX_BUCKETS);
+        assert!(limiter.check(Client::Account(AccountId(0)), now).is_err());
+        assert!(limiter.check(Client::Account(AccountId(1)), now).is_ok());
+    }
+
+    #[test]
+    fn zero_turns_the_limit_off() {
+        let limiter = RateLimiter::new(Limit(0), Limit(0));
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            assert!(limiter.check(Client::Account(AccountId(1)), now).is_ok());
+        }
+    }
+}
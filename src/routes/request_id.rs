@@ -0,0 +1,135 @@
+use std::convert::Infallible;
+
+use tracing::{
+    field::{display, Empty},
+    Span,
+};
+use warp::{
+    http::{header::HeaderValue, HeaderMap},
+    reply::Response,
+    trace::{Info, Trace},
+    Filter, Rejection,
+};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// Ids sent by clients or proxies longer than this are replaced by our own
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Wraps the whole request in a span, which `request_id` fills in with the id
+/// so that every log entry of the request carries it
+pub fn trace_request() -> Trace<impl Fn(Info<'_>) -> Span + Clone> {
+    warp::trace(|info: Info<'_>| {
+        tracing::info_span!(
+            "request",
+            request_id = Empty,
+            method = %info.method(),
+            path = %info.path(),
+        )
+    })
+}
+
+/// Takes the id a proxy in front of the service assigned to the request,
+/// or makes up a new one
+pub fn request_id() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        let id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        Span::current().record("request_id", display(&id));
+        id
+    })
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Answers rejections with their problem details and echoes the request id
+/// back, so a client can point at the log entries of its request
+pub async fn reply_with_request_id(
+    request_id: String,
+    reply: Result<Response, Rejection>,
+) -> Result<Response, Rejection> {
+    let mut res = match reply {
+        Ok(res) => res,
+        Err(rejection) => handle_errors::return_error(rejection, request_id.clone()).await?,
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod request_id_tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{is_valid_request_id, request_id, trace_request};
+    use warp::Filter;
+
+    /// Collects the formatted log lines of a test
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn handler_logs_carry_request_id() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+
+        let filter = request_id()
+            .map(|id: String| {
+                tracing::info!("handling the request");
+                id
+            })
+            .with(trace_request());
+        let res = warp::test::request()
+            .header("X-Request-Id", "abc-123")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.body(), "abc-123");
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("handling the request"))
+            .unwrap();
+        assert!(line.contains("request_id=abc-123"), "{}", line);
+    }
+
+    #[test]
+    fn request_ids_from_clients() {
+        assert!(is_valid_request_id("8f14e45f-ceea-467f-a8f4-23e0a7b3c1d2"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
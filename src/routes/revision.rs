@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use handle_errors::Error;
+use warp::reply::Response;
+
+use crate::routes::question::{changed_question_reply, if_match_version};
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::etag::extract_if_match;
+use crate::types::question::UpdateQuestion;
+use crate::types::revision::diff_revisions;
+use crate::types::tag::normalize_tags;
+use crate::types::validation::Validate;
+
+pub async fn get_question_revisions(
+    id: i32,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_question_revisions(id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Compares two revisions of a question given as `from` and `to` query params
+/// `/questions/1/revisions/diff?from=1&to=3`
+pub async fn diff_question_revisions(
+    id: i32,
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let (from, to) = match (params.get("from"), params.get("to")) {
+        (Some(from), Some(to)) => (
+            from.parse::<i32>().map_err(Error::ParseError)?,
+            to.parse::<i32>().map_err(Error::ParseError)?,
+        ),
+        _ => return Err(warp::reject::custom(Error::MissingParameters)),
+    };
+
+    let from = store.get_question_revision(id, from).await?;
+    let to = store.get_question_revision(id, to).await?;
+
+    Ok(warp::reply::json(&diff_revisions(&from, &to)))
+}
+
+/// Restores the question to an earlier revision, which is recorded as a new one.
+/// Takes `If-Match` like an update, so it can't undo edits the client hasn't seen.
+pub async fn rollback_question(
+    id: i32,
+    revision: i32,
+    if_match: Option<String>,
+    session: Session,
+    store: Store,
+) -> Result<Response, warp::Rejection> {
+    let if_match = extract_if_match(if_match.as_deref())?;
+    let is_moderator = session.is_moderator();
+    let account_id = session.account_id;
+
+    if is_moderator || store.is_question_owner(id, &account_id).await? {
+        let revision = store.get_question_revision(id, revision).await?;
+        // Older revisions predate the checks of today, they have to pass them again
+        let question = UpdateQuestion {
+            title: revision.title,
+            content: revision.content,
+            tags: revision.tags.map(normalize_tags),
+        };
+        question.validate()?;
+
+        let version = if_match_version(&store, id, &if_match).await?;
+        match store
+            .update_question(question, id, account_id, version)
+            .await
+        {
+            Ok(res) => Ok(changed_question_reply(&store, res).await?),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+pub async fn get_answer_revisions(
+    id: i32,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_answer_revisions(id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
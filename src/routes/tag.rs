@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use handle_errors::Error;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    store::Store,
+    types::pagination::extract_list_query,
+};
+
+/// Number of suggestions returned when no `limit` is given
+const DEFAULT_SUGGESTIONS: u32 = 10;
+
+#[instrument]
+pub async fn get_tags(
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "querying tags");
+    let list = extract_list_query(&params)?;
+    list.ensure_offset_only()?;
+
+    match store.get_tags(list.limit, list.offset).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+#[instrument]
+pub async fn suggest_tags(
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let prefix = match params.get("prefix") {
+        Some(prefix) => prefix.trim().to_lowercase(),
+        None => return Err(warp::reject::custom(Error::MissingParameters)),
+    };
+
+    let limit = match params.get("limit") {
+        Some(limit) => limit.parse::<u32>().map_err(Error::ParseError)?,
+        None => DEFAULT_SUGGESTIONS,
+    };
+
+    event!(Level::INFO, prefix = prefix.as_str(), "suggesting tags");
+
+    match store.suggest_tags(&prefix, limit).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
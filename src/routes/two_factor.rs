@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use handle_errors::Error;
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::{
+    routes::authentication::{add_failed_login, hash_token, start_session},
+    services::totp::{
+        base32_encode, generate_recovery_codes, generate_secret, otpauth_uri, verify_code,
+        SecretCipher,
+    },
+    store::Store,
+    types::{
+        account::{AccountId, Session},
+        two_factor::{TwoFactor, TwoFactorCode, TwoFactorEnrollment, VerifyLoginChallenge},
+    },
+};
+
+/// Wrong codes a login challenge takes before it stops working
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+/// Starts the enrollment, it only takes effect once confirmed with a code
+pub async fn enroll(
+    session: Session,
+    store: Store,
+    cipher: Option<Arc<SecretCipher>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let cipher = cipher.ok_or(Error::TwoFactorNotConfigured)?;
+    let account_id = session.account_id;
+
+    if let Some(two_factor) = store.get_two_factor(&account_id).await? {
+        if two_factor.confirmed {
+            return Err(warp::reject::custom(Error::InvalidParameter(
+                "two-factor authentication is already enabled".to_string(),
+            )));
+        }
+    }
+
+    let account = store.get_account_by_id(&account_id).await?;
+    let secret = generate_secret();
+    let recovery_codes = generate_recovery_codes();
+    let recovery_code_hashes = recovery_codes
+        .iter()
+        .map(|code| hash_token(code))
+        .collect::<Vec<String>>();
+
+    store
+        .enroll_two_factor(&account_id, &cipher.encrypt(&secret), &recovery_code_hashes)
+        .await?;
+
+    Ok(warp::reply::json(&TwoFactorEnrollment {
+        otpauth_uri: otpauth_uri(&secret, &account.email),
+        secret: base32_encode(&secret),
+        recovery_codes,
+    }))
+}
+
+/// Turns two-factor authentication on with a first code of the authenticator app
+pub async fn confirm(
+    session: Session,
+    store: Store,
+    cipher: Option<Arc<SecretCipher>>,
+    code: TwoFactorCode,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let cipher = cipher.ok_or(Error::TwoFactorNotConfigured)?;
+    let account_id = session.account_id;
+
+    let two_factor = match store.get_two_factor(&account_id).await? {
+        Some(two_factor) if !two_factor.confirmed => two_factor,
+        _ => {
+            return Err(warp::reject::custom(Error::InvalidParameter(
+                "there is no two-factor enrollment to confirm".to_string(),
+            )))
+        }
+    };
+
+    if !check_code(&store, &cipher, &account_id, &two_factor, &code.code).await? {
+        return Err(warp::reject::custom(Error::InvalidTwoFactorCode));
+    }
+
+    store.confirm_two_factor(&account_id).await?;
+    event!(
+        Level::INFO,
+        account_id = account_id.0,
+        "two-factor authentication enabled"
+    );
+
+    Ok(warp::reply::with_status(
+        "Two-factor authentication enabled",
+        StatusCode::OK,
+    ))
+}
+
+/// Turns two-factor authentication off, which takes a code as well
+pub async fn disable(
+    session: Session,
+    store: Store,
+    cipher: Option<Arc<SecretCipher>>,
+    code: TwoFactorCode,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let cipher = cipher.ok_or(Error::TwoFactorNotConfigured)?;
+    let account_id = session.account_id;
+
+    let two_factor = store.get_two_factor(&account_id).await?.ok_or_else(|| {
+        Error::InvalidParameter("two-factor authentication is not enabled".to_string())
+    })?;
+
+    if !check_code(&store, &cipher, &account_id, &two_factor, &code.code).await? {
+        return Err(warp::reject::custom(Error::InvalidTwoFactorCode));
+    }
+
+    store.delete_two_factor(&account_id).await?;
+    event!(
+        Level::INFO,
+        account_id = account_id.0,
+        "two-factor authentication disabled"
+    );
+
+    Ok(warp::reply::with_status(
+        "Two-factor authentication disabled",
+        StatusCode::OK,
+    ))
+}
+
+/// Second step of the login, trades the challenge and a code for a token pair.
+/// Wrong codes count as failed logins of the account's email.
+pub async fn verify_login(
+    store: Store,
+    cipher: Option<Arc<SecretCipher>>,
+    verify: VerifyLoginChallenge,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let cipher = cipher.ok_or(Error::TwoFactorNotConfigured)?;
+    let challenge_hash = hash_token(&verify.challenge_token);
+
+    let (account_id, role) = store
+        .get_login_challenge(&challenge_hash, MAX_CHALLENGE_ATTEMPTS)
+        .await?
+        .ok_or(Error::InvalidToken)?;
+
+    let account = store.get_account_by_id(&account_id).await?;
+    let email_key = format!("email:{}", account.email.to_lowercase());
+    if let Some(seconds) = store
+        .login_locked_for(std::slice::from_ref(&email_key))
+        .await?
+    {
+        return Err(warp::reject::custom(Error::TooManyLoginAttempts(seconds)));
+    }
+
+    let two_factor = store
+        .get_two_factor(&account_id)
+        .await?
+        .ok_or(Error::InvalidToken)?;
+
+    if !check_code(&store, &cipher, &account_id, &two_factor, &verify.code).await? {
+        store.fail_login_challenge(&challenge_hash).await?;
+        add_failed_login(&store, &[email_key]).await?;
+        return Err(warp::reject::custom(Error::InvalidTwoFactorCode));
+    }
+
+    if !store.delete_login_challenge(&challenge_hash).await? {
+        return Err(warp::reject::custom(Error::InvalidToken));
+    }
+    store.clear_failed_logins(&email_key).await?;
+
+    let tokens = start_session(&store, account_id, role).await?;
+    Ok(warp::reply::json(&tokens))
+}
+
+/// Checks a code of the authenticator app, or a recovery code once the
+/// enrollment is confirmed. Either only works once.
+async fn check_code(
+    store: &Store,
+    cipher: &SecretCipher,
+    account_id: &AccountId,
+    two_factor: &TwoFactor,
+    code: &str,
+) -> Result<bool, Error> {
+    let code = code.trim();
+    let secret = cipher.decrypt(&two_factor.secret)?;
+
+    match verify_code(
+        &secret,
+        code,
+        Utc::now().timestamp() as u64,
+        two_factor.last_used_step,
+    ) {
+        Some(step) => store.use_totp_step(account_id, step as i64).await,
+        None if two_factor.confirmed => {
+            store
+                .use_recovery_code(account_id, &hash_token(&code.to_lowercase()))
+                .await
+        }
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,75 @@
+use handle_errors::Error;
+
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::vote::{NewVote, VoteTarget};
+
+pub async fn vote_question(
+    id: i32,
+    session: Session,
+    store: Store,
+    new_vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+
+    if store.is_question_owner(id, &account_id).await? {
+        return Err(warp::reject::custom(Error::CannotVoteOwnPost));
+    }
+
+    match store
+        .vote(VoteTarget::Question(id), &account_id, new_vote.direction)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn withdraw_question_vote(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store
+        .withdraw_vote(VoteTarget::Question(id), &session.account_id)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn vote_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    new_vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+
+    if store.is_answer_owner(id, &account_id).await? {
+        return Err(warp::reject::custom(Error::CannotVoteOwnPost));
+    }
+
+    match store
+        .vote(VoteTarget::Answer(id), &account_id, new_vote.direction)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn withdraw_answer_vote(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store
+        .withdraw_vote(VoteTarget::Answer(id), &session.account_id)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,114 @@
+use std::{fmt::Debug, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use handle_errors::Error;
+use tracing::{event, Level};
+
+use crate::config::Config;
+
+/// Address the mails of the service are sent from
+const MAIL_FROM: &str = "no-reply@rust-question-api.local";
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers the mails of the service, so the transport can be swapped
+/// without touching the routes
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), Error>;
+}
+
+/// Writes every mail into the tracing log, meant for local development
+#[derive(Debug, Clone)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), Error> {
+        event!(
+            Level::INFO,
+            to = mail.to.as_str(),
+            subject = mail.subject.as_str(),
+            body = mail.body.as_str(),
+            "sending mail"
+        );
+        Ok(())
+    }
+}
+
+/// Stores every mail as an `.eml` file in a directory, so tests
+/// and developers can pick up the tokens sent out
+#[derive(Debug, Clone)]
+pub struct FileMailer {
+    pub dir: PathBuf,
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(Error::MailError)?;
+
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S%.f"),
+            mail.to.replace(|c: char| !c.is_ascii_alphanumeric() && c != '@' && c != '.', "_")
+        ));
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            MAIL_FROM, mail.to, mail.subject, mail.body
+        );
+
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(Error::MailError)?;
+
+        event!(Level::INFO, path = %path.display(), "mail written");
+        Ok(())
+    }
+}
+
+/// Picks the mailer set up in the config, falling back to the log
+pub fn from_config(config: &Config) -> Arc<dyn Mailer> {
+    match &config.mail_dir {
+        Some(dir) => Arc::new(FileMailer {
+            dir: PathBuf::from(dir),
+        }),
+        None => Arc::new(LogMailer),
+    }
+}
+
+#[cfg(test)]
+mod mailer_tests {
+    use super::{FileMailer, Mail, Mailer};
+
+    #[tokio::test]
+    async fn file_mailer_writes_mail() {
+        let dir = std::env::temp_dir().join(format!("mails-{}", uuid::Uuid::new_v4()));
+        let mailer = FileMailer { dir: dir.clone() };
+
+        mailer
+            .send(Mail {
+                to: "test@email.com".to_string(),
+                subject: "Verify your email".to_string(),
+                body: "some token".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut files = std::fs::read_dir(&dir).unwrap();
+        let mail = std::fs::read_to_string(files.next().unwrap().unwrap().path()).unwrap();
+        assert!(mail.contains("To: test@email.com\r\n"));
+        assert!(mail.contains("Subject: Verify your email\r\n"));
+        assert!(mail.ends_with("some token\r\n"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
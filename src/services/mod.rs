@@ -1 +1,4 @@
-pub mod profanity;
\ No newline at end of file
+pub mod mailer;
+pub mod oidc;
+pub mod profanity;
+pub mod totp;
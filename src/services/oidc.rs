@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use handle_errors::Error;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// The parts of the provider's discovery document the login flow needs
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The `aud` claim, providers send either a single client or a list
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == client_id,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+/// The claims of an ID token the service relies on
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: Audience,
+    pub exp: i64,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// Client side of the OpenID Connect authorization code flow
+#[derive(Debug, Clone)]
+pub struct OidcClient {
+    pub issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    http: reqwest::Client,
+}
+
+impl OidcClient {
+    pub fn new(
+        issuer: String,
+        client_id: String,
+        client_secret: Option<String>,
+        redirect_url: String,
+    ) -> OidcClient {
+        OidcClient {
+            issuer,
+            client_id,
+            client_secret,
+            redirect_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Single sign-on is only offered when the provider and the client are configured
+    pub fn from_config(config: &Config) -> Option<Arc<OidcClient>> {
+        match (
+            &config.oidc_issuer,
+            &config.oidc_client_id,
+            &config.oidc_redirect_url,
+        ) {
+            (Some(issuer), Some(client_id), Some(redirect_url)) => Some(Arc::new(OidcClient::new(
+                issuer.clone(),
+                client_id.clone(),
+                config.oidc_client_secret.clone(),
+                redirect_url.clone(),
+            ))),
+            _ => None,
+        }
+    }
+
+    async fn discover(&self) -> Result<ProviderMetadata, Error> {
+        let metadata = self
+            .http
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                self.issuer.trim_end_matches('/')
+            ))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(Error::ReqwestApiError)?
+            .json::<ProviderMetadata>()
+            .await
+            .map_err(Error::ReqwestApiError)?;
+
+        if metadata.issuer != self.issuer {
+            return Err(Error::OidcLoginFailed(format!(
+                "provider calls itself `{}`",
+                metadata.issuer
+            )));
+        }
+        Ok(metadata)
+    }
+
+    /// Where the browser is sent to sign in at the provider
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+        login_hint: Option<&str>,
+    ) -> Result<String, Error> {
+        let metadata = self.discover().await?;
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint).map_err(|_| {
+            Error::OidcLoginFailed("authorization endpoint is not a valid URL".to_string())
+        })?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", "openid email profile")
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &code_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        if let Some(login_hint) = login_hint {
+            url.query_pairs_mut().append_pair("login_hint", login_hint);
+        }
+
+        Ok(url.to_string())
+    }
+
+    /// Trades the code the provider sent back for the claims of its ID token
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, Error> {
+        let metadata = self.discover().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let res = self
+            .http
+            .post(metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(Error::ReqwestApiError)?;
+
+        if !res.status().is_success() {
+            return Err(Error::OidcLoginFailed(format!(
+                "token endpoint replied with {}",
+                res.status()
+            )));
+        }
+
+        let token = res
+            .json::<TokenResponse>()
+            .await
+            .map_err(Error::ReqwestApiError)?;
+
+        verify_id_token(
+            &token.id_token,
+            &self.issuer,
+            &self.client_id,
+            nonce,
+            Utc::now().timestamp(),
+        )
+    }
+}
+
+/// PKCE `S256` challenge of a code verifier
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// Reads the claims of an ID token which came straight from the token endpoint.
+/// Its signature is not checked, OIDC Core 3.1.3.7 lets the connection to the
+/// provider vouch for it in that case, but it has to be meant for this client
+/// and this login.
+pub fn verify_id_token(
+    id_token: &str,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<IdTokenClaims, Error> {
+    let invalid = |reason: &str| Error::OidcLoginFailed(format!("ID token {}", reason));
+
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| invalid("is malformed"))?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| invalid("is malformed"))?;
+    let claims = serde_json::from_slice::<IdTokenClaims>(&payload)
+        .map_err(|_| invalid("is missing claims"))?;
+
+    if claims.iss != issuer {
+        return Err(invalid("comes from another issuer"));
+    }
+    if !claims.aud.contains(client_id) {
+        return Err(invalid("is meant for another client"));
+    }
+    if claims.exp <= now {
+        return Err(invalid("has expired"));
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(invalid("belongs to another login"));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod oidc_tests {
+    use super::{code_challenge, verify_id_token, OidcClient};
+
+    use mock_server::{MockServer, OIDC_DEFAULT_EMAIL};
+
+    const ISSUER: &str = "http://127.0.0.1:8082";
+
+    fn id_token(claims: serde_json::Value) -> String {
+        format!(
+            "e30.{}.",
+            base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    #[test]
+    fn pkce_challenge() {
+        // Example of RFC 7636, appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn id_token_checks() {
+        let token = id_token(serde_json::json!({
+            "iss": ISSUER,
+            "sub": "42",
+            "aud": ["other-client", "question-api"],
+            "exp": 2000,
+            "nonce": "n-0S6_WzA2Mj",
+            "email": "tess@example.com",
+            "email_verified": true
+        }));
+
+        let claims = verify_id_token(&token, ISSUER, "question-api", "n-0S6_WzA2Mj", 1000).unwrap();
+        assert_eq!(claims.sub, "42");
+        assert_eq!(claims.email.as_deref(), Some("tess@example.com"));
+        assert!(claims.email_verified);
+
+        assert!(
+            verify_id_token(&token, "http://evil", "question-api", "n-0S6_WzA2Mj", 1000).is_err()
+        );
+        assert!(verify_id_token(&token, ISSUER, "another-api", "n-0S6_WzA2Mj", 1000).is_err());
+        assert!(verify_id_token(&token, ISSUER, "question-api", "replayed", 1000).is_err());
+        assert!(verify_id_token(&token, ISSUER, "question-api", "n-0S6_WzA2Mj", 2000).is_err());
+        assert!(
+            verify_id_token("not a token", ISSUER, "question-api", "n-0S6_WzA2Mj", 1000).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn login_with_mock_provider() {
+        let socket = "127.0.0.1:8082".parse().expect("Not a valid address");
+        let handler = MockServer::new(socket).oneshot();
+
+        let client = OidcClient::new(
+            ISSUER.to_string(),
+            "question-api".to_string(),
+            None,
+            "http://localhost:8080/auth/oidc/callback".to_string(),
+        );
+        let url = client
+            .authorization_url("some-state", "some-nonce", "some-verifier", None)
+            .await
+            .unwrap();
+
+        let res = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(url)
+            .send()
+            .await
+            .unwrap();
+        let location = reqwest::Url::parse(res.headers()["Location"].to_str().unwrap()).unwrap();
+        let params: std::collections::HashMap<_, _> = location.query_pairs().into_owned().collect();
+        assert_eq!(params["state"], "some-state");
+
+        assert!(client
+            .exchange_code(&params["code"], "another-verifier", "some-nonce")
+            .await
+            .is_err());
+
+        let claims = client
+            .exchange_code(&params["code"], "some-verifier", "some-nonce")
+            .await
+            .unwrap();
+        assert_eq!(claims.email.as_deref(), Some(OIDC_DEFAULT_EMAIL));
+
+        let _ = handler.sender.send(1);
+    }
+}
@@ -43,7 +43,7 @@ pub async fn check_profanity(content: String) -> Result<String, handle_errors::E
         .body(content)
         .send()
         .await
-        .map_err(|e| handle_errors::Error::MiddlewareReqwestApiError(e))?;
+        .map_err(handle_errors::Error::MiddlewareReqwestApiError)?;
 
     if !res.status().is_success() {
         if res.status().is_client_error() {
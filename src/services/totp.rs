@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use handle_errors::Error;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// Seconds a code stays valid, the default of authenticator apps
+pub const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_DIGITS: usize = 6;
+/// Codes of the steps right before and after the current one are accepted
+/// as well, so a clock running a bit off doesn't lock anyone out
+const TOTP_SKEW_STEPS: u64 = 1;
+const SECRET_BYTES: usize = 20;
+const RECOVERY_CODES: usize = 10;
+/// Name the accounts show up under in authenticator apps
+const TOTP_ISSUER: &str = "rust-question-api";
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const NONCE_BYTES: usize = 24;
+
+pub fn generate_secret() -> Vec<u8> {
+    rand::thread_rng().gen::<[u8; SECRET_BYTES]>().to_vec()
+}
+
+/// One-time codes which stand in for a TOTP code, to get in without the device
+pub fn generate_recovery_codes() -> Vec<String> {
+    const CHARS: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    let mut part = || -> String {
+        (0..5)
+            .map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char)
+            .collect()
+    };
+
+    (0..RECOVERY_CODES)
+        .map(|_| format!("{}-{}", part(), part()))
+        .collect()
+}
+
+/// The code of a time step as described in RFC 6238, with HMAC-SHA1 and 6 digits
+pub fn totp_code(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS as u32),
+        width = TOTP_DIGITS
+    )
+}
+
+/// Returns the time step the code belongs to when it is valid at `now`,
+/// a step can only be used once so it has to be after `last_step`
+pub fn verify_code(secret: &[u8], code: &str, now: u64, last_step: Option<i64>) -> Option<u64> {
+    if code.len() != TOTP_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = now / TOTP_STEP_SECONDS;
+    (current.saturating_sub(TOTP_SKEW_STEPS)..=current + TOTP_SKEW_STEPS)
+        .filter(|step| last_step.is_none_or(|last_step| *step as i64 > last_step))
+        .find(|step| totp_code(secret, *step) == code)
+}
+
+/// The URI authenticator apps scan from a QR code
+pub fn otpauth_uri(secret: &[u8], account_name: &str) -> String {
+    let mut uri = reqwest::Url::parse("otpauth://totp/").expect("Not a valid URI");
+    uri.set_path(&format!("/{}:{}", TOTP_ISSUER, account_name));
+    uri.query_pairs_mut()
+        .append_pair("secret", &base32_encode(secret))
+        .append_pair("issuer", TOTP_ISSUER)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &TOTP_DIGITS.to_string())
+        .append_pair("period", &TOTP_STEP_SECONDS.to_string());
+
+    uri.to_string()
+}
+
+/// Base32 without padding, the way authenticator apps expect the secret
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Encrypts the TOTP secrets before they are stored, so the database alone
+/// isn't enough to generate codes
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl SecretCipher {
+    /// Any passphrase works, the cipher key is derived from it
+    pub fn new(key: &str) -> SecretCipher {
+        let key = Sha256::digest(key.as_bytes());
+        SecretCipher {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+
+    /// Two-factor authentication is only offered when a key is configured
+    pub fn from_config(config: &Config) -> Option<Arc<SecretCipher>> {
+        config
+            .two_factor_key
+            .as_deref()
+            .map(|key| Arc::new(SecretCipher::new(key)))
+    }
+
+    pub fn encrypt(&self, secret: &[u8]) -> String {
+        let nonce = rand::thread_rng().gen::<[u8; NONCE_BYTES]>();
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(
+            self.cipher
+                .encrypt(XNonce::from_slice(&nonce), secret)
+                .expect("Cannot encrypt secret"),
+        );
+
+        base64::encode(encrypted)
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<Vec<u8>, Error> {
+        let encrypted = base64::decode(encrypted).map_err(|_| Error::CannotDecryptToken)?;
+        if encrypted.len() < NONCE_BYTES {
+            return Err(Error::CannotDecryptToken);
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_BYTES);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::CannotDecryptToken)
+    }
+}
+
+#[cfg(test)]
+mod totp_tests {
+    use super::{base32_encode, otpauth_uri, totp_code, verify_code, SecretCipher};
+
+    // Secret of the test vectors in RFC 6238, appendix B
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc_6238_codes() {
+        assert_eq!(totp_code(SECRET, 59 / 30), "287082");
+        assert_eq!(totp_code(SECRET, 1111111109 / 30), "081804");
+        assert_eq!(totp_code(SECRET, 1234567890 / 30), "005924");
+        assert_eq!(totp_code(SECRET, 2000000000 / 30), "279037");
+    }
+
+    #[test]
+    fn codes_are_checked_once_within_skew() {
+        let now = 1111111109;
+        let step = now / 30;
+
+        assert_eq!(verify_code(SECRET, "081804", now, None), Some(step));
+        assert_eq!(verify_code(SECRET, "081804", now + 30, None), Some(step));
+        assert_eq!(verify_code(SECRET, "081804", now + 90, None), None);
+        assert_eq!(verify_code(SECRET, "081804", now, Some(step as i64)), None);
+        assert_eq!(verify_code(SECRET, "81804", now, None), None);
+    }
+
+    #[test]
+    fn otpauth_uri_with_base32_secret() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(
+            otpauth_uri(SECRET, "tess@example.com"),
+            "otpauth://totp/rust-question-api:tess@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=rust-question-api&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn secrets_are_encrypted() {
+        let cipher = SecretCipher::new("RANDOM WORDS WINTER MACINTOSH PC");
+        let encrypted = cipher.encrypt(SECRET);
+
+        assert_ne!(encrypted, cipher.encrypt(SECRET));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), SECRET);
+        assert!(SecretCipher::new("another key")
+            .decrypt(&encrypted)
+            .is_err());
+    }
+}
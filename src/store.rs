@@ -1,10 +1,283 @@
 use handle_errors::Error;
-use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
-use sqlx::Row;
+use sqlx::postgres::{PgArguments, PgPool, PgPoolOptions, PgRow};
+use sqlx::query::Query;
+use sqlx::{Postgres, Row, Transaction};
 
-use crate::types::account::{Account, AccountId};
-use crate::types::answer::{Answer, AnswerId, NewAnswer};
-use crate::types::question::{NewQuestion, Question, QuestionId};
+use crate::types::account::{
+    Account, AccountId, AccountSummary, ExternalIdentity, Profile, Role, TokenPurpose,
+    UpdateProfile,
+};
+use crate::types::answer::{Answer, AnswerId, NewAnswer, UpdateAnswer};
+use crate::types::api_key::{ApiKey, ApiKeyAccess, ApiKeyId, ApiKeyScope, NewApiKey};
+use crate::types::comment::{Comment, CommentId, CommentTarget, NewComment};
+use crate::types::pagination::{Cursor, ListQuery, Page, Sort};
+use crate::types::question::{
+    NewQuestion, Question, QuestionFilter, QuestionId, QuestionMatch, UpdateQuestion,
+};
+use crate::types::revision::{AnswerRevision, QuestionRevision};
+use crate::types::tag::{Tag, TagMatch};
+use crate::types::two_factor::TwoFactor;
+use crate::types::vote::{Score, VoteDirection, VoteTarget};
+
+/// Sum of the votes cast on each row of `questions`
+const QUESTION_SCORE: &str = "(SELECT COALESCE(SUM(v.value), 0) FROM votes v
+        WHERE v.question_id = questions.id) AS score";
+
+/// Sum of the votes cast on each row of `answers`
+const ANSWER_SCORE: &str = "(SELECT COALESCE(SUM(v.value), 0) FROM votes v
+        WHERE v.answer_id = answers.id) AS score";
+
+/// Per question figures the listings can be sorted by
+const QUESTION_STATS: &str = "(SELECT COUNT(*) FROM answers a
+        WHERE a.question_id = questions.id) AS answer_count,
+    GREATEST(questions.created_on, (SELECT MAX(a.created_on) FROM answers a
+        WHERE a.question_id = questions.id)) AS last_activity_on";
+
+/// Postgres error codes of the constraint violations the store tells apart
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+fn violates(e: &sqlx::Error, code: &str) -> bool {
+    e.as_database_error().and_then(|err| err.code()).as_deref() == Some(code)
+}
+
+fn order_by(sort: Sort) -> &'static str {
+    match sort {
+        Sort::Newest => "created_on DESC, id DESC",
+        Sort::Oldest => "created_on ASC, id ASC",
+        Sort::MostAnswered => "answer_count DESC, created_on DESC, id DESC",
+        Sort::RecentlyActive => "last_activity_on DESC, id DESC",
+        Sort::MostVoted => "score DESC, created_on DESC, id DESC",
+    }
+}
+
+/// How rows compare to the cursor to come after it in the given order
+fn cursor_comparison(sort: Sort) -> &'static str {
+    match sort {
+        Sort::Oldest => ">",
+        _ => "<",
+    }
+}
+
+/// Condition applying a `QuestionFilter`, its values are bound by
+/// `bind_question_filter` starting at placeholder `$first`
+fn question_filter_condition(first: usize) -> String {
+    format!(
+        "(${tags}::text[] IS NULL OR (${all} AND tags @> ${tags}) OR (NOT ${all} AND tags && ${tags}))
+        AND (${answered}::boolean IS NULL OR (accepted_answer_id IS NOT NULL) = ${answered})",
+        tags = first,
+        all = first + 1,
+        answered = first + 2
+    )
+}
+
+fn bind_question_filter<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    filter: &'q QuestionFilter,
+) -> Query<'q, Postgres, PgArguments> {
+    query
+        .bind(&filter.tags)
+        .bind(filter.tag_match == TagMatch::All)
+        .bind(filter.answered)
+}
+
+/// Column of `votes` referencing the target and the id it has to match
+fn vote_column(target: VoteTarget) -> (&'static str, i32) {
+    match target {
+        VoteTarget::Question(id) => ("question_id", id),
+        VoteTarget::Answer(id) => ("answer_id", id),
+    }
+}
+
+/// The post a vote or comment was meant for, as named by `NotFound`
+fn post_name(column: &str, id: i32) -> String {
+    format!("{} {}", column.trim_end_matches("_id"), id)
+}
+
+/// Column of `comments` referencing the target and the id it has to match
+fn comment_column(target: CommentTarget) -> (&'static str, i32) {
+    match target {
+        CommentTarget::Question(id) => ("question_id", id),
+        CommentTarget::Answer(id) => ("answer_id", id),
+    }
+}
+
+fn question_from_row(row: &PgRow) -> Question {
+    Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        score: row.get("score"),
+        accepted_answer_id: row
+            .get::<Option<i32>, _>("accepted_answer_id")
+            .map(AnswerId),
+        version: row.get("version"),
+    }
+}
+
+fn answer_from_row(row: &PgRow) -> Answer {
+    Answer {
+        id: AnswerId(row.get("id")),
+        content: row.get("content"),
+        question_id: QuestionId(row.get("question_id")),
+        account_id: AccountId(row.get("account_id")),
+        created_on: row.get("created_on"),
+        score: row.get("score"),
+    }
+}
+
+fn comment_from_row(row: &PgRow) -> Comment {
+    Comment {
+        id: CommentId(row.get("id")),
+        content: row.get("content"),
+        account_id: AccountId(row.get("account_id")),
+        question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+        answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+        parent_id: row.get::<Option<i32>, _>("parent_id").map(CommentId),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn account_from_row(row: &PgRow) -> Account {
+    Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+        role: Role::parse(row.get("role")).unwrap_or_default(),
+        display_name: row.get("display_name"),
+        bio: row.get("bio"),
+        avatar_url: row.get("avatar_url"),
+    }
+}
+
+fn profile_from_row(row: &PgRow) -> Profile {
+    Profile {
+        id: AccountId(row.get("id")),
+        email: row.get("email"),
+        role: Role::parse(row.get("role")).unwrap_or_default(),
+        display_name: row.get("display_name"),
+        bio: row.get("bio"),
+        avatar_url: row.get("avatar_url"),
+        email_verified: row.get("email_verified"),
+    }
+}
+
+fn api_key_from_row(row: &PgRow) -> ApiKey {
+    ApiKey {
+        id: ApiKeyId(row.get("id")),
+        name: row.get("name"),
+        prefix: row.get("prefix"),
+        scope: ApiKeyScope::parse(row.get("scope")).unwrap_or(ApiKeyScope::Read),
+        created_on: row.get("created_on"),
+        expires_on: row.get("expires_on"),
+        last_used_on: row.get("last_used_on"),
+    }
+}
+
+fn question_revision_from_row(row: &PgRow) -> QuestionRevision {
+    QuestionRevision {
+        question_id: QuestionId(row.get("question_id")),
+        revision: row.get("revision"),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        account_id: AccountId(row.get("account_id")),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn database_error(e: sqlx::Error) -> Error {
+    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+    Error::DatabaseQueryError(e)
+}
+
+/// Fails with `NotFound` unless the post `column` refers to exists, keeping
+/// it from being deleted until the transaction ends. Lists of a missing post
+/// would otherwise just come back empty
+async fn ensure_post_exists(
+    tx: &mut Transaction<'_, Postgres>,
+    column: &str,
+    id: i32,
+) -> Result<(), Error> {
+    let table = match column {
+        "question_id" => "questions",
+        _ => "answers",
+    };
+
+    match sqlx::query(&format!("SELECT 1 FROM {} WHERE id = $1 FOR KEY SHARE", table))
+        .bind(id)
+        .fetch_optional(tx)
+        .await
+    {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Error::NotFound(post_name(column, id))),
+        Err(e) => Err(database_error(e)),
+    }
+}
+
+/// Records the current state of the question as its next revision
+async fn add_question_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    question: &Question,
+    account_id: &AccountId,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id)
+        SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4, $5
+        FROM question_revisions WHERE question_id = $1",
+    )
+    .bind(question.id.0)
+    .bind(&question.title)
+    .bind(&question.content)
+    .bind(&question.tags)
+    .bind(account_id.0)
+    .execute(tx)
+    .await
+    .map_err(database_error)?;
+
+    Ok(())
+}
+
+/// Records the current state of the answer as its next revision
+async fn add_answer_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    answer: &Answer,
+    account_id: &AccountId,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO answer_revisions (answer_id, revision, content, account_id)
+        SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3
+        FROM answer_revisions WHERE answer_id = $1",
+    )
+    .bind(answer.id.0)
+    .bind(&answer.content)
+    .bind(account_id.0)
+    .execute(tx)
+    .await
+    .map_err(database_error)?;
+
+    Ok(())
+}
+
+/// Consumes a mailed token, a token can only be used once and before it expires
+async fn use_account_token(
+    tx: &mut Transaction<'_, Postgres>,
+    purpose: TokenPurpose,
+    token_hash: &str,
+) -> Result<Option<AccountId>, Error> {
+    sqlx::query(
+        "UPDATE account_tokens SET used_on = NOW()
+        WHERE token_hash = $1 AND purpose = $2 AND used_on IS NULL AND expires_on > NOW()
+        RETURNING account_id",
+    )
+    .bind(token_hash)
+    .bind(purpose.as_str())
+    .map(|row: PgRow| AccountId(row.get("account_id")))
+    .fetch_optional(tx)
+    .await
+    .map_err(database_error)
+}
 
 #[derive(Debug, Clone)]
 pub struct Store {
@@ -26,22 +299,54 @@ impl Store {
 
     pub async fn get_questions(
         &self,
-        limit: Option<u32>,
-        offset: u32,
-    ) -> Result<Vec<Question>, handle_errors::Error> {
-        match sqlx::query("SELECT * from questions LIMIT $1 OFFSET $2")
-            .bind(limit)
-            .bind(offset)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
+        list: &ListQuery,
+        filter: &QuestionFilter,
+    ) -> Result<Page<Question>, handle_errors::Error> {
+        let sort = list.sort.unwrap_or(Sort::Newest);
+        // The SQL is only ever formatted with static fragments, all values are bound
+        let sql = format!(
+            "SELECT *, {}, {} from questions
+            WHERE ($3::timestamp IS NULL OR (created_on, id) {} ($3, $4))
+            AND {}
+            ORDER BY {}
+            LIMIT $1 OFFSET $2",
+            QUESTION_SCORE,
+            QUESTION_STATS,
+            cursor_comparison(sort),
+            question_filter_condition(5),
+            order_by(sort),
+        );
+
+        // One extra row tells whether there is a next page
+        let query = sqlx::query(&sql)
+            .bind(list.limit.map(|limit| limit.saturating_add(1)))
+            .bind(list.offset)
+            .bind(list.cursor.as_ref().map(|cursor| cursor.created_on))
+            .bind(list.cursor.as_ref().map(|cursor| cursor.id));
+
+        let total = self.count_questions(None, filter).await?;
+
+        match bind_question_filter(query, filter)
+            .map(|row: PgRow| {
+                (
+                    question_from_row(&row),
+                    Cursor {
+                        created_on: row.get("created_on"),
+                        id: row.get("id"),
+                    },
+                )
             })
             .fetch_all(&self.connection)
             .await
         {
-            Ok(questions) => Ok(questions),
+            Ok(questions) if sort.supports_cursor() => {
+                Ok(Page::with_cursors(questions, list.limit, total))
+            }
+            Ok(questions) => Ok(Page::new(
+                questions.into_iter().map(|(question, _)| question).collect(),
+                list.limit,
+                total,
+            )),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(handle_errors::Error::DatabaseQueryError(e))
@@ -49,62 +354,325 @@ impl Store {
         }
     }
 
+    pub async fn search_questions(
+        &self,
+        search: &str,
+        list: &ListQuery,
+        filter: &QuestionFilter,
+    ) -> Result<Page<QuestionMatch>, Error> {
+        // Results are ordered by relevance unless a sort is asked for
+        let sql = format!(
+            "SELECT id, title, content, tags, accepted_answer_id, version, {}, {},
+                ts_rank(search_vector, query) AS rank,
+                ts_headline('english',
+                    concat_ws(' ', title, content,
+                        (SELECT string_agg(a.content, ' ') FROM answers a
+                        WHERE a.question_id = questions.id)),
+                    query, 'MaxFragments=2, MinWords=5, MaxWords=20') AS snippet
+            FROM questions, websearch_to_tsquery('english', $1) query
+            WHERE search_vector @@ query
+            AND {}
+            ORDER BY {}
+            LIMIT $2 OFFSET $3",
+            QUESTION_SCORE,
+            QUESTION_STATS,
+            question_filter_condition(4),
+            list.sort.map_or("rank DESC, id", order_by),
+        );
+
+        let query = sqlx::query(&sql)
+            .bind(search)
+            .bind(list.limit.map(|limit| limit.saturating_add(1)))
+            .bind(list.offset);
+
+        let total = self.count_questions(Some(search), filter).await?;
+
+        match bind_question_filter(query, filter)
+            .map(|row: PgRow| QuestionMatch {
+                question: question_from_row(&row),
+                rank: row.get("rank"),
+                snippet: row.get("snippet"),
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(matches) => Ok(Page::new(matches, list.limit, total)),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Number of questions matching the filter and, if given, the search query
+    async fn count_questions(
+        &self,
+        search: Option<&str>,
+        filter: &QuestionFilter,
+    ) -> Result<i64, Error> {
+        let sql = format!(
+            "SELECT COUNT(*) AS total from questions
+            WHERE ($1::text IS NULL OR search_vector @@ websearch_to_tsquery('english', $1))
+            AND {}",
+            question_filter_condition(2),
+        );
+
+        match bind_question_filter(sqlx::query(&sql).bind(search), filter)
+            .map(|row: PgRow| row.get("total"))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(total) => Ok(total),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_tags(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Tag>, Error> {
+        match sqlx::query(
+            "SELECT tag, COUNT(*) AS count
+            FROM questions, unnest(tags) AS tag
+            GROUP BY tag
+            ORDER BY count DESC, tag
+            LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| Tag {
+            name: row.get("tag"),
+            count: row.get("count"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn suggest_tags(&self, prefix: &str, limit: u32) -> Result<Vec<Tag>, Error> {
+        // Escape the LIKE wildcards so the prefix is matched literally
+        let pattern = format!(
+            "{}%",
+            prefix
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        match sqlx::query(
+            "SELECT tag, COUNT(*) AS count
+            FROM questions, unnest(tags) AS tag
+            WHERE tag LIKE $1
+            GROUP BY tag
+            ORDER BY count DESC, tag
+            LIMIT $2",
+        )
+        .bind(pattern)
+        .bind(limit)
+        .map(|row: PgRow| Tag {
+            name: row.get("tag"),
+            count: row.get("count"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_question(&self, question_id: i32) -> Result<Question, Error> {
+        match sqlx::query(&format!(
+            "SELECT id, title, content, tags, accepted_answer_id, version, {}
+            from questions WHERE id = $1",
+            QUESTION_SCORE
+        ))
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(question) => Ok(question),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
     pub async fn add_question(
         &self,
         new_question: NewQuestion,
         account_id: AccountId,
     ) -> Result<Question, Error> {
-        match sqlx::query(
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let question = sqlx::query(
             "INSERT INTO questions (title, content, tags, account_id)
                  VALUES ($1, $2, $3, $4)
-                 RETURNING id, title, content, tags",
+                 RETURNING id, title, content, tags, accepted_answer_id, version,
+                    0::bigint AS score",
         )
         .bind(new_question.title)
         .bind(new_question.content)
         .bind(new_question.tags)
         .bind(account_id.0)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_one(&self.connection)
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_one(&mut tx)
         .await
-        {
-            Ok(question) => Ok(question),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
+        .map_err(database_error)?;
+
+        add_question_revision(&mut tx, &question, &account_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(question)
     }
+
+    /// Updates the question and records the new state as its next revision,
+    /// made by `account_id` which is either the owner or a moderator.
+    /// Fails with `PreconditionFailed` when the question has moved on from
+    /// `version`, `None` when any version will do.
     pub async fn update_question(
         &self,
-        question: Question,
+        question: UpdateQuestion,
         question_id: i32,
         account_id: AccountId,
+        version: Option<i32>,
     ) -> Result<Question, Error> {
-        match sqlx::query(
-            "UPDATE questions SET title = $1, content = $2, tags = $3
-        WHERE id = $4 and account_id = $5
-        RETURNING id, title, content, tags",
-        )
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let question = sqlx::query(&format!(
+            "UPDATE questions SET title = $1, content = $2, tags = $3, version = version + 1
+        WHERE id = $4 AND ($5::integer IS NULL OR version = $5)
+        RETURNING id, title, content, tags, accepted_answer_id, version, {}",
+            QUESTION_SCORE
+        ))
         .bind(question.title)
         .bind(question.content)
         .bind(question.tags)
         .bind(question_id)
-        .bind(account_id.0)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
+        .bind(version)
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        let question = match question {
+            Some(question) => question,
+            None => return Err(self.missing_or_changed(question_id).await),
+        };
+
+        add_question_revision(&mut tx, &question, &account_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(question)
+    }
+
+    pub async fn get_question_revisions(
+        &self,
+        question_id: i32,
+    ) -> Result<Vec<QuestionRevision>, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        ensure_post_exists(&mut tx, "question_id", question_id).await?;
+
+        let revisions = sqlx::query(
+            "SELECT question_id, revision, title, content, tags, account_id, created_on
+            FROM question_revisions WHERE question_id = $1
+            ORDER BY revision",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| question_revision_from_row(&row))
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(revisions)
+    }
+
+    pub async fn get_question_revision(
+        &self,
+        question_id: i32,
+        revision: i32,
+    ) -> Result<QuestionRevision, Error> {
+        match sqlx::query(
+            "SELECT question_id, revision, title, content, tags, account_id, created_on
+            FROM question_revisions WHERE question_id = $1 AND revision = $2",
+        )
+        .bind(question_id)
+        .bind(revision)
+        .map(|row: PgRow| question_revision_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(revision) => Ok(revision),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_answer_revisions(&self, answer_id: i32) -> Result<Vec<AnswerRevision>, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        ensure_post_exists(&mut tx, "answer_id", answer_id).await?;
+
+        let revisions = sqlx::query(
+            "SELECT answer_id, revision, content, account_id, created_on
+            FROM answer_revisions WHERE answer_id = $1
+            ORDER BY revision",
+        )
+        .bind(answer_id)
+        .map(|row: PgRow| AnswerRevision {
+            answer_id: AnswerId(row.get("answer_id")),
+            revision: row.get("revision"),
             content: row.get("content"),
-            tags: row.get("tags"),
+            account_id: AccountId(row.get("account_id")),
+            created_on: row.get("created_on"),
         })
-        .fetch_one(&self.connection)
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(revisions)
+    }
+
+    /// Marks the answer as the solution of the question it was posted to
+    pub async fn accept_answer(&self, question_id: i32, answer_id: i32) -> Result<Question, Error> {
+        match sqlx::query(&format!(
+            "UPDATE questions SET accepted_answer_id = $2, version = version + 1
+            WHERE id = $1
+            AND EXISTS (SELECT 1 FROM answers WHERE id = $2 AND question_id = $1)
+            RETURNING id, title, content, tags, accepted_answer_id, version, {}",
+            QUESTION_SCORE
+        ))
+        .bind(question_id)
+        .bind(answer_id)
+        .map(|row: PgRow| question_from_row(&row))
+        .fetch_optional(&self.connection)
         .await
         {
-            Ok(question) => Ok(question),
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => match self.missing_or_changed(question_id).await {
+                // The question is there, the answer was posted to another one
+                Error::PreconditionFailed => Err(Error::InvalidParameter(format!(
+                    "answer {} was not posted to question {}",
+                    answer_id, question_id
+                ))),
+                e => Err(e),
+            },
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -112,13 +680,31 @@ impl Store {
         }
     }
 
-    pub async fn delete_question(&self, question_id: i32) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1")
-            .bind(question_id)
-            .execute(&self.connection)
-            .await
+    /// Returns whether there was a question to delete, fails with
+    /// `PreconditionFailed` when it has moved on from `version`
+    pub async fn delete_question(
+        &self,
+        question_id: i32,
+        version: Option<i32>,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "DELETE FROM questions
+            WHERE id = $1 AND ($2::integer IS NULL OR version = $2)",
+        )
+        .bind(question_id)
+        .bind(version)
+        .execute(&self.connection)
+        .await
         {
-            Ok(_) => Ok(true),
+            Ok(result) if result.rows_affected() > 0 => Ok(true),
+            Ok(_) => match self.missing_or_changed(question_id).await {
+                Error::NotFound(_) => Ok(false),
+                e => Err(e),
+            },
+            Err(e) if violates(&e, FOREIGN_KEY_VIOLATION) => Err(Error::Conflict(format!(
+                "question {} still has answers",
+                question_id
+            ))),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -126,27 +712,88 @@ impl Store {
         }
     }
 
+    /// Tells why a conditional write matched no question
+    async fn missing_or_changed(&self, question_id: i32) -> Error {
+        match sqlx::query("SELECT 1 FROM questions WHERE id = $1")
+            .bind(question_id)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(_)) => Error::PreconditionFailed,
+            Ok(None) => Error::NotFound(format!("question {}", question_id)),
+            Err(e) => database_error(e),
+        }
+    }
+
     pub async fn add_answer(
         &self,
         new_answer: NewAnswer,
         account_id: AccountId,
     ) -> Result<Answer, Error> {
-        match sqlx::query(
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        let question_id = new_answer.question_id.0;
+
+        let answer = sqlx::query(
             "INSERT INTO answers (content, question_id, account_id) 
-            VALUES ($1, $2, $3) returning id, content, question_id",
+            VALUES ($1, $2, $3)
+            RETURNING id, content, question_id, account_id, created_on, 0::bigint AS score",
         )
         .bind(new_answer.content)
-        .bind(new_answer.question_id.0)
+        .bind(question_id)
         .bind(account_id.0)
-        .map(|row: PgRow| Answer {
-            id: AnswerId(row.get("id")),
-            content: row.get("content"),
-            question_id: QuestionId(row.get("question_id")),
-        })
-        .fetch_one(&self.connection)
+        .map(|row: PgRow| answer_from_row(&row))
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| match e {
+            e if violates(&e, FOREIGN_KEY_VIOLATION) => {
+                Error::NotFound(format!("question {}", question_id))
+            }
+            e => database_error(e),
+        })?;
+
+        add_answer_revision(&mut tx, &answer, &account_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(answer)
+    }
+
+    /// Updates the answer and records the new state as its next revision,
+    /// made by `account_id` which is either the owner or a moderator
+    pub async fn update_answer(
+        &self,
+        answer: UpdateAnswer,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Answer, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let answer = sqlx::query(&format!(
+            "UPDATE answers SET content = $1
+            WHERE id = $2
+            RETURNING id, content, question_id, account_id, created_on, {}",
+            ANSWER_SCORE
+        ))
+        .bind(answer.content)
+        .bind(answer_id)
+        .map(|row: PgRow| answer_from_row(&row))
+        .fetch_one(&mut tx)
         .await
+        .map_err(database_error)?;
+
+        add_answer_revision(&mut tx, &answer, &account_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(answer)
+    }
+
+    /// Returns whether there was an answer to delete
+    pub async fn delete_answer(&self, answer_id: i32) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM answers WHERE id = $1")
+            .bind(answer_id)
+            .execute(&self.connection)
+            .await
         {
-            Ok(answer) => Ok(answer),
+            Ok(result) => Ok(result.rows_affected() > 0),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -154,17 +801,53 @@ impl Store {
         }
     }
 
-    pub async fn add_account(self, account: Account) -> Result<bool, Error> {
+    pub async fn get_answers(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Answer>, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        ensure_post_exists(&mut tx, "question_id", question_id).await?;
+
+        let answers = sqlx::query(&format!(
+            "SELECT id, content, question_id, account_id, created_on, {} from answers
+            WHERE question_id = $1
+            ORDER BY created_on, id
+            LIMIT $2 OFFSET $3",
+            ANSWER_SCORE
+        ))
+        .bind(question_id)
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| answer_from_row(&row))
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(answers)
+    }
+
+    pub async fn add_account(self, account: Account) -> Result<AccountId, Error> {
         match sqlx::query(
-            "INSERT INTO accounts (email, password) 
-            VALUES ($1, $2)  returning id, email",
+            "INSERT INTO accounts (email, password, display_name, bio, avatar_url) 
+            VALUES ($1, $2, $3, $4, $5)  returning id, email",
         )
         .bind(account.email)
         .bind(account.password)
-        .execute(&self.connection)
+        .bind(account.display_name)
+        .bind(account.bio)
+        .bind(account.avatar_url)
+        .map(|row: PgRow| AccountId(row.get("id")))
+        .fetch_one(&self.connection)
         .await
         {
-            Ok(_) => Ok(true),
+            Ok(account_id) => Ok(account_id),
+            Err(error) if violates(&error, UNIQUE_VIOLATION) => Err(Error::Conflict(
+                "an account with this email already exists".to_string(),
+            )),
             Err(error) => {
                 tracing::event!(
                     tracing::Level::ERROR,
@@ -184,13 +867,9 @@ impl Store {
     }
 
     pub async fn get_account(self, email: String) -> Result<Account, Error> {
-        match sqlx::query("SELECT *  from accounts where email = $1")
+        match sqlx::query("SELECT * from accounts where email = $1 AND deleted_on IS NULL")
             .bind(email)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                email: row.get("email"),
-                password: row.get("password"),
-            })
+            .map(|row: PgRow| account_from_row(&row))
             .fetch_one(&self.connection)
             .await
         {
@@ -202,18 +881,1288 @@ impl Store {
         }
     }
 
-    pub async fn is_question_owner(
-        &self,
-        question_id: i32,
-        account_id: &AccountId,
-    ) -> Result<bool, Error> {
-        match sqlx::query("SELECT * from questions where id = $1 and account_id = $2")
-            .bind(question_id)
+    pub async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where id = $1 AND deleted_on IS NULL")
             .bind(account_id.0)
+            .map(|row: PgRow| account_from_row(&row))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_profile(&self, account_id: &AccountId) -> Result<Profile, Error> {
+        match sqlx::query(
+            "SELECT id, email, role, display_name, bio, avatar_url,
+                email_verified_on IS NOT NULL AS email_verified
+            FROM accounts WHERE id = $1 AND deleted_on IS NULL",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| profile_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(profile) => Ok(profile),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn update_profile(
+        &self,
+        account_id: &AccountId,
+        profile: UpdateProfile,
+    ) -> Result<Profile, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET display_name = $1, bio = $2, avatar_url = $3
+            WHERE id = $4 AND deleted_on IS NULL
+            RETURNING id, email, role, display_name, bio, avatar_url,
+                email_verified_on IS NOT NULL AS email_verified",
+        )
+        .bind(profile.display_name)
+        .bind(profile.bio)
+        .bind(profile.avatar_url)
+        .bind(account_id.0)
+        .map(|row: PgRow| profile_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(profile) => Ok(profile),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Sets a new password and ends every other session of the account
+    pub async fn update_password(
+        &self,
+        account_id: &AccountId,
+        password: String,
+        current_jti: &str,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE account_id = $1 AND jti <> $2 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(current_jti)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// Deletes the personal data of the account while keeping its row, so the
+    /// questions, answers and comments it posted stay around without an author
+    /// anyone can identify. Its sessions, mailed tokens, API keys,
+    /// linked sign-on identities and two-factor settings are dropped as well.
+    pub async fn anonymize_account(&self, account_id: &AccountId) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        // Failed logins are counted by email, which is about to be replaced
+        sqlx::query(
+            "DELETE FROM login_attempts
+            WHERE key = (SELECT 'email:' || lower(email) FROM accounts WHERE id = $1)",
+        )
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        sqlx::query(
+            "UPDATE accounts SET
+                email = 'deleted-' || id || '@deleted.invalid',
+                password = '',
+                display_name = NULL,
+                bio = NULL,
+                avatar_url = NULL,
+                email_verified_on = NULL,
+                role = 'user',
+                deleted_on = NOW()
+            WHERE id = $1",
+        )
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM account_tokens WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM api_keys WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM account_identities WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM two_factor WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM login_challenges WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// Seconds left until the longest running lockout among `keys` ends
+    pub async fn login_locked_for(&self, keys: &[String]) -> Result<Option<u64>, Error> {
+        match sqlx::query(
+            "SELECT CEIL(EXTRACT(EPOCH FROM MAX(locked_until) - NOW()))::bigint AS seconds
+            FROM login_attempts
+            WHERE key = ANY($1) AND locked_until > NOW()",
+        )
+        .bind(keys)
+        .map(|row: PgRow| row.get::<Option<i64>, _>("seconds"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(seconds) => Ok(seconds.map(|seconds| seconds.max(1) as u64)),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Counts a failed login for `key`, failures older than `window_seconds`
+    /// are forgotten. Returns the number of failures in a row.
+    pub async fn add_failed_login(&self, key: &str, window_seconds: i32) -> Result<i32, Error> {
+        match sqlx::query(
+            "INSERT INTO login_attempts (key, failures, last_failure_on)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (key) DO UPDATE SET
+                failures = CASE
+                    WHEN login_attempts.last_failure_on < NOW() - make_interval(secs => $2)
+                    THEN 1
+                    ELSE login_attempts.failures + 1
+                END,
+                last_failure_on = NOW()
+            RETURNING failures",
+        )
+        .bind(key)
+        .bind(window_seconds as f64)
+        .map(|row: PgRow| row.get("failures"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(failures) => Ok(failures),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn lock_login(&self, key: &str, seconds: u64) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE login_attempts SET locked_until = NOW() + make_interval(secs => $2)
+            WHERE key = $1",
+        )
+        .bind(key)
+        .bind(seconds as f64)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn clear_failed_logins(&self, key: &str) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(key)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_api_key(
+        &self,
+        account_id: &AccountId,
+        new_api_key: NewApiKey,
+        prefix: &str,
+        key_hash: &str,
+    ) -> Result<ApiKey, Error> {
+        match sqlx::query(
+            "INSERT INTO api_keys (account_id, name, prefix, key_hash, scope, expires_on)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(days => $6))
+            RETURNING id, name, prefix, scope, created_on, expires_on, last_used_on",
+        )
+        .bind(account_id.0)
+        .bind(new_api_key.name)
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(new_api_key.scope.as_str())
+        .bind(new_api_key.expires_in_days.map(|days| days as i32))
+        .map(|row: PgRow| api_key_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(api_key) => Ok(api_key),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_api_keys(&self, account_id: &AccountId) -> Result<Vec<ApiKey>, Error> {
+        match sqlx::query(
+            "SELECT id, name, prefix, scope, created_on, expires_on, last_used_on
+            FROM api_keys WHERE account_id = $1
+            ORDER BY id",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| api_key_from_row(&row))
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(api_keys) => Ok(api_keys),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns `false` when the account has no such key
+    pub async fn delete_api_key(&self, id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM api_keys WHERE id = $1 AND account_id = $2")
+            .bind(id)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// The account an unexpired key belongs to, without recording a use
+    pub async fn api_key_account(&self, key_hash: &str) -> Result<Option<AccountId>, Error> {
+        match sqlx::query(
+            "SELECT account_id FROM api_keys
+            WHERE key_hash = $1 AND (expires_on IS NULL OR expires_on > NOW())",
+        )
+        .bind(key_hash)
+        .map(|row: PgRow| AccountId(row.get("account_id")))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(account_id) => Ok(account_id),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Looks up an unexpired key of an open account, recording that it got used
+    pub async fn use_api_key(&self, key_hash: &str) -> Result<Option<ApiKeyAccess>, Error> {
+        match sqlx::query(
+            "UPDATE api_keys SET last_used_on = NOW()
+            FROM accounts
+            WHERE api_keys.key_hash = $1
+                AND (api_keys.expires_on IS NULL OR api_keys.expires_on > NOW())
+                AND accounts.id = api_keys.account_id
+                AND accounts.deleted_on IS NULL
+            RETURNING api_keys.id, api_keys.account_id, api_keys.scope,
+                api_keys.expires_on, accounts.role",
+        )
+        .bind(key_hash)
+        .map(|row: PgRow| ApiKeyAccess {
+            id: ApiKeyId(row.get("id")),
+            account_id: AccountId(row.get("account_id")),
+            role: Role::parse(row.get("role")).unwrap_or_default(),
+            scope: ApiKeyScope::parse(row.get("scope")).unwrap_or(ApiKeyScope::Read),
+            expires_on: row.get("expires_on"),
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(access) => Ok(access),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_two_factor(&self, account_id: &AccountId) -> Result<Option<TwoFactor>, Error> {
+        match sqlx::query(
+            "SELECT secret, confirmed_on IS NOT NULL AS confirmed, last_used_step
+            FROM two_factor WHERE account_id = $1",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| TwoFactor {
+            secret: row.get("secret"),
+            confirmed: row.get("confirmed"),
+            last_used_step: row.get("last_used_step"),
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(two_factor) => Ok(two_factor),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Whether any account logs in with two-factor authentication
+    pub async fn has_two_factor(&self) -> Result<bool, Error> {
+        match sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM two_factor WHERE confirmed_on IS NOT NULL) AS used",
+        )
+        .map(|row: PgRow| row.get("used"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(used) => Ok(used),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Starts an enrollment, replacing one which was never confirmed
+    pub async fn enroll_two_factor(
+        &self,
+        account_id: &AccountId,
+        secret: &str,
+        recovery_code_hashes: &[String],
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        sqlx::query(
+            "INSERT INTO two_factor (account_id, secret) VALUES ($1, $2)
+            ON CONFLICT (account_id) DO UPDATE
+            SET secret = EXCLUDED.secret, created_on = NOW(), last_used_step = NULL
+            WHERE two_factor.confirmed_on IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(secret)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        for code_hash in recovery_code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (account_id, code_hash) VALUES ($1, $2)")
+                .bind(account_id.0)
+                .bind(code_hash)
+                .execute(&mut tx)
+                .await
+                .map_err(database_error)?;
+        }
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    pub async fn confirm_two_factor(&self, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query("UPDATE two_factor SET confirmed_on = NOW() WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn delete_two_factor(&self, account_id: &AccountId) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        sqlx::query("DELETE FROM two_factor WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// Records the time step of a code, returns `false` when it or a later
+    /// one got used already, so each code only works once
+    pub async fn use_totp_step(&self, account_id: &AccountId, step: i64) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE two_factor SET last_used_step = $2
+            WHERE account_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+        )
+        .bind(account_id.0)
+        .bind(step)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns `false` when the account has no such unused recovery code
+    pub async fn use_recovery_code(
+        &self,
+        account_id: &AccountId,
+        code_hash: &str,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE recovery_codes SET used_on = NOW()
+            WHERE account_id = $1 AND code_hash = $2 AND used_on IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(code_hash)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_login_challenge(
+        &self,
+        challenge_hash: &str,
+        account_id: &AccountId,
+        valid_minutes: i32,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        sqlx::query("DELETE FROM login_challenges WHERE expires_on <= NOW()")
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query(
+            "INSERT INTO login_challenges (challenge_hash, account_id, expires_on)
+            VALUES ($1, $2, NOW() + make_interval(mins => $3))",
+        )
+        .bind(challenge_hash)
+        .bind(account_id.0)
+        .bind(valid_minutes)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// The account a pending login challenge belongs to, as long as it
+    /// hasn't expired or seen too many wrong codes
+    pub async fn get_login_challenge(
+        &self,
+        challenge_hash: &str,
+        max_failed_attempts: i32,
+    ) -> Result<Option<(AccountId, Role)>, Error> {
+        match sqlx::query(
+            "SELECT accounts.id, accounts.role FROM login_challenges
+            JOIN accounts ON accounts.id = login_challenges.account_id
+            WHERE login_challenges.challenge_hash = $1
+                AND login_challenges.expires_on > NOW()
+                AND login_challenges.failed_attempts < $2
+                AND accounts.deleted_on IS NULL",
+        )
+        .bind(challenge_hash)
+        .bind(max_failed_attempts)
+        .map(|row: PgRow| {
+            (
+                AccountId(row.get("id")),
+                Role::parse(row.get("role")).unwrap_or_default(),
+            )
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(challenge) => Ok(challenge),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn fail_login_challenge(&self, challenge_hash: &str) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE login_challenges SET failed_attempts = failed_attempts + 1
+            WHERE challenge_hash = $1",
+        )
+        .bind(challenge_hash)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns `false` when the challenge was already used up, so it can't be finished twice
+    pub async fn delete_login_challenge(&self, challenge_hash: &str) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM login_challenges WHERE challenge_hash = $1")
+            .bind(challenge_hash)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Remembers a login started at the OpenID Connect provider, dropping
+    /// the ones which were never finished
+    pub async fn add_oidc_login(
+        &self,
+        state_hash: &str,
+        nonce: &str,
+        code_verifier: &str,
+        valid_minutes: i32,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        sqlx::query("DELETE FROM oidc_logins WHERE expires_on <= NOW()")
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query(
+            "INSERT INTO oidc_logins (state_hash, nonce, code_verifier, expires_on)
+            VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))",
+        )
+        .bind(state_hash)
+        .bind(nonce)
+        .bind(code_verifier)
+        .bind(valid_minutes)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// Returns the nonce and code verifier of a pending login, which can only be finished once
+    pub async fn take_oidc_login(
+        &self,
+        state_hash: &str,
+    ) -> Result<Option<(String, String)>, Error> {
+        match sqlx::query(
+            "DELETE FROM oidc_logins WHERE state_hash = $1 AND expires_on > NOW()
+            RETURNING nonce, code_verifier",
+        )
+        .bind(state_hash)
+        .map(|row: PgRow| (row.get("nonce"), row.get("code_verifier")))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(login) => Ok(login),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Finds the account linked to the identity. Otherwise the identity gets linked
+    /// to the account with the same email, or to a new account when there is none.
+    /// Returns `None` when the email is taken but the provider didn't verify it.
+    pub async fn sign_in_with_identity(
+        &self,
+        identity: ExternalIdentity,
+    ) -> Result<Option<(AccountId, Role)>, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let linked = sqlx::query(
+            "SELECT accounts.id, accounts.role FROM account_identities
+            JOIN accounts ON accounts.id = account_identities.account_id
+            WHERE account_identities.issuer = $1 AND account_identities.subject = $2
+                AND accounts.deleted_on IS NULL",
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .map(|row: PgRow| {
+            (
+                AccountId(row.get("id")),
+                Role::parse(row.get("role")).unwrap_or_default(),
+            )
+        })
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        if linked.is_some() {
+            return Ok(linked);
+        }
+
+        let existing =
+            sqlx::query("SELECT id, role FROM accounts WHERE email = $1 AND deleted_on IS NULL")
+                .bind(&identity.email)
+                .map(|row: PgRow| {
+                    (
+                        AccountId(row.get("id")),
+                        Role::parse(row.get("role")).unwrap_or_default(),
+                    )
+                })
+                .fetch_optional(&mut tx)
+                .await
+                .map_err(database_error)?;
+
+        let account = match existing {
+            Some(_) if !identity.email_verified => return Ok(None),
+            Some(account) => {
+                sqlx::query(
+                    "UPDATE accounts SET email_verified_on = COALESCE(email_verified_on, NOW())
+                    WHERE id = $1",
+                )
+                .bind((account.0).0)
+                .execute(&mut tx)
+                .await
+                .map_err(database_error)?;
+                account
+            }
+            // Without a password the account can only be used through the provider,
+            // until one gets set with a password reset
+            None => sqlx::query(
+                "INSERT INTO accounts (email, password, display_name, email_verified_on)
+                VALUES ($1, '', $2, CASE WHEN $3 THEN NOW() END)
+                RETURNING id, role",
+            )
+            .bind(&identity.email)
+            .bind(&identity.display_name)
+            .bind(identity.email_verified)
+            .map(|row: PgRow| {
+                (
+                    AccountId(row.get("id")),
+                    Role::parse(row.get("role")).unwrap_or_default(),
+                )
+            })
+            .fetch_one(&mut tx)
+            .await
+            .map_err(database_error)?,
+        };
+
+        sqlx::query(
+            "INSERT INTO account_identities (issuer, subject, account_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (issuer, subject) DO UPDATE SET account_id = EXCLUDED.account_id",
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind((account.0).0)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(Some(account))
+    }
+
+    pub async fn add_account_token(
+        &self,
+        account_id: &AccountId,
+        purpose: TokenPurpose,
+        token_hash: &str,
+        valid_hours: i32,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO account_tokens (account_id, purpose, token_hash, expires_on)
+            VALUES ($1, $2, $3, NOW() + make_interval(hours => $4))",
+        )
+        .bind(account_id.0)
+        .bind(purpose.as_str())
+        .bind(token_hash)
+        .bind(valid_hours)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Marks the email of the account the token was sent to as verified,
+    /// returns `false` when the token is unknown, used or expired
+    pub async fn verify_email(&self, token_hash: &str) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let account_id =
+            match use_account_token(&mut tx, TokenPurpose::VerifyEmail, token_hash).await? {
+                Some(account_id) => account_id,
+                None => return Ok(false),
+            };
+
+        sqlx::query("UPDATE accounts SET email_verified_on = NOW() WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    /// Sets the new password of the account the token was sent to and ends
+    /// all of its sessions, returns `false` when the token is unknown, used or expired
+    pub async fn reset_password(&self, token_hash: &str, password: String) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let account_id =
+            match use_account_token(&mut tx, TokenPurpose::ResetPassword, token_hash).await? {
+                Some(account_id) => account_id,
+                None => return Ok(false),
+            };
+
+        sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    pub async fn get_accounts(
+        &self,
+        role: Option<Role>,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<AccountSummary>, Error> {
+        match sqlx::query(
+            "SELECT id, email, role FROM accounts
+            WHERE deleted_on IS NULL AND ($1::text IS NULL OR role = $1)
+            ORDER BY id
+            LIMIT $2 OFFSET $3",
+        )
+        .bind(role.map(|role| role.as_str()))
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| AccountSummary {
+            id: AccountId(row.get("id")),
+            email: row.get("email"),
+            role: Role::parse(row.get("role")).unwrap_or_default(),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(accounts) => Ok(accounts),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn set_role(&self, account_id: i32, role: Role) -> Result<AccountSummary, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET role = $1 WHERE id = $2
+            RETURNING id, email, role",
+        )
+        .bind(role.as_str())
+        .bind(account_id)
+        .map(|row: PgRow| AccountSummary {
+            id: AccountId(row.get("id")),
+            email: row.get("email"),
+            role: Role::parse(row.get("role")).unwrap_or_default(),
+        })
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_refresh_token(
+        &self,
+        jti: &str,
+        token_hash: &str,
+        account_id: &AccountId,
+        valid_days: i32,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO refresh_tokens (jti, token_hash, account_id, expires_on)
+            VALUES ($1, $2, $3, NOW() + make_interval(days => $4))",
+        )
+        .bind(jti)
+        .bind(token_hash)
+        .bind(account_id.0)
+        .bind(valid_days)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Revokes the refresh token and stores its replacement in one go, so a
+    /// refresh token can only ever be exchanged once. Returns `None` when the
+    /// token is unknown, expired or already used.
+    pub async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        jti: &str,
+        new_token_hash: &str,
+        valid_days: i32,
+    ) -> Result<Option<(AccountId, Role)>, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let account = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE token_hash = $1 AND revoked_on IS NULL AND expires_on > NOW()
+            RETURNING account_id,
+                (SELECT role FROM accounts WHERE accounts.id = refresh_tokens.account_id) AS role",
+        )
+        .bind(token_hash)
+        .map(|row: PgRow| {
+            (
+                AccountId(row.get("account_id")),
+                Role::parse(row.get("role")).unwrap_or_default(),
+            )
+        })
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        let (account_id, role) = match account {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (jti, token_hash, account_id, expires_on)
+            VALUES ($1, $2, $3, NOW() + make_interval(days => $4))",
+        )
+        .bind(jti)
+        .bind(new_token_hash)
+        .bind(account_id.0)
+        .bind(valid_days)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(Some((account_id, role)))
+    }
+
+    /// A session stays active until its refresh token is used, revoked or expires
+    pub async fn is_session_active(&self, jti: &str) -> Result<bool, Error> {
+        match sqlx::query(
+            "SELECT jti FROM refresh_tokens
+            WHERE jti = $1 AND revoked_on IS NULL AND expires_on > NOW()",
+        )
+        .bind(jti)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(session) => Ok(session.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn revoke_session(&self, jti: &str) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE jti = $1 AND revoked_on IS NULL",
+        )
+        .bind(jti)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn revoke_all_sessions(&self, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE refresh_tokens SET revoked_on = NOW()
+            WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Whether the account posted the question, which has to exist
+    pub async fn is_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT account_id from questions where id = $1")
+            .bind(question_id)
+            .map(|row: PgRow| row.get::<Option<i32>, _>("account_id"))
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(owner)) => Ok(owner == Some(account_id.0)),
+            Ok(None) => Err(Error::NotFound(format!("question {}", question_id))),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Whether the account posted the answer, which has to exist
+    pub async fn is_answer_owner(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT account_id from answers where id = $1")
+            .bind(answer_id)
+            .map(|row: PgRow| row.get::<Option<i32>, _>("account_id"))
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(owner)) => Ok(owner == Some(account_id.0)),
+            Ok(None) => Err(Error::NotFound(format!("answer {}", answer_id))),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Casts the vote of the account, replacing any vote it already cast on the target
+    pub async fn vote(
+        &self,
+        target: VoteTarget,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<Score, Error> {
+        let (column, target_id) = vote_column(target);
+
+        match sqlx::query(&format!(
+            "INSERT INTO votes (account_id, {column}, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (account_id, {column}) DO UPDATE SET value = EXCLUDED.value",
+            column = column
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .bind(direction.value())
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => self.score(target).await,
+            Err(e) if violates(&e, FOREIGN_KEY_VIOLATION) => {
+                Err(Error::NotFound(post_name(column, target_id)))
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn withdraw_vote(
+        &self,
+        target: VoteTarget,
+        account_id: &AccountId,
+    ) -> Result<Score, Error> {
+        let (column, target_id) = vote_column(target);
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        ensure_post_exists(&mut tx, column, target_id).await?;
+
+        sqlx::query(&format!(
+            "DELETE FROM votes WHERE account_id = $1 AND {} = $2",
+            column
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .execute(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        self.score(target).await
+    }
+
+    async fn score(&self, target: VoteTarget) -> Result<Score, Error> {
+        let (column, target_id) = vote_column(target);
+
+        match sqlx::query(&format!(
+            "SELECT COALESCE(SUM(value), 0) AS score FROM votes WHERE {} = $1",
+            column
+        ))
+        .bind(target_id)
+        .map(|row: PgRow| Score {
+            score: row.get("score"),
+        })
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(score) => Ok(score),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Adds a comment to the target, a reply has to be on the same post as its parent
+    pub async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(id) => (Some(id), None),
+            CommentTarget::Answer(id) => (None, Some(id)),
+        };
+        let parent_id = new_comment.parent_id.map(|parent_id| parent_id.0);
+
+        match sqlx::query(
+            "INSERT INTO comments (content, account_id, question_id, answer_id, parent_id)
+            SELECT $1, $2, $3, $4, $5
+            WHERE $5::integer IS NULL OR EXISTS (
+                SELECT 1 FROM comments parent WHERE parent.id = $5
+                AND parent.question_id IS NOT DISTINCT FROM $3::integer
+                AND parent.answer_id IS NOT DISTINCT FROM $4::integer
+            )
+            RETURNING id, content, account_id, question_id, answer_id, parent_id, created_on",
+        )
+        .bind(new_comment.content)
+        .bind(account_id.0)
+        .bind(question_id)
+        .bind(answer_id)
+        .bind(parent_id)
+        .map(|row: PgRow| comment_from_row(&row))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(comment)) => Ok(comment),
+            Ok(None) => Err(Error::InvalidParameter(format!(
+                "comment {} does not belong to this post",
+                parent_id.unwrap_or_default()
+            ))),
+            Err(e) if violates(&e, FOREIGN_KEY_VIOLATION) => {
+                let (column, target_id) = comment_column(target);
+                Err(Error::NotFound(post_name(column, target_id)))
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_comments(&self, target: CommentTarget) -> Result<Vec<Comment>, Error> {
+        let (column, target_id) = comment_column(target);
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        ensure_post_exists(&mut tx, column, target_id).await?;
+
+        let comments = sqlx::query(&format!(
+            "SELECT id, content, account_id, question_id, answer_id, parent_id, created_on
+            FROM comments WHERE {} = $1
+            ORDER BY created_on, id",
+            column
+        ))
+        .bind(target_id)
+        .map(|row: PgRow| comment_from_row(&row))
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(comments)
+    }
+
+    pub async fn update_comment(
+        &self,
+        content: String,
+        comment_id: i32,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        match sqlx::query(
+            "UPDATE comments SET content = $1
+            WHERE id = $2 and account_id = $3
+            RETURNING id, content, account_id, question_id, answer_id, parent_id, created_on",
+        )
+        .bind(content)
+        .bind(comment_id)
+        .bind(account_id.0)
+        .map(|row: PgRow| comment_from_row(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(comment) => Ok(comment),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns whether there was a comment to delete
+    pub async fn delete_comment(&self, comment_id: i32) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM comments WHERE id = $1")
+            .bind(comment_id)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Whether the account posted the comment, which has to exist
+    pub async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT account_id from comments where id = $1")
+            .bind(comment_id)
+            .map(|row: PgRow| row.get::<Option<i32>, _>("account_id"))
             .fetch_optional(&self.connection)
             .await
         {
-            Ok(question) => Ok(question.is_some()),
+            Ok(Some(owner)) => Ok(owner == Some(account_id.0)),
+            Ok(None) => Err(Error::NotFound(format!("comment {}", comment_id))),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -1,11 +1,32 @@
 use chrono::{DateTime, Utc};
+use handle_errors::Error;
 use serde::{Serialize, Deserialize};
 
+use crate::types::{
+    api_key::API_KEY_SESSION_PREFIX,
+    validation::{Validate, Violations, MAX_BIO_LENGTH, MAX_DISPLAY_NAME_LENGTH},
+};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
     pub nbf: DateTime<Utc>,
+    /// Identifies the login the token belongs to, so it can be revoked
+    pub jti: String,
+    pub role: Role,
+}
+
+impl Session {
+    /// Moderators may edit and delete posts of other accounts
+    pub fn is_moderator(&self) -> bool {
+        self.role >= Role::Moderator
+    }
+
+    /// The request was made with an API key rather than a login
+    pub fn is_api_key(&self) -> bool {
+        self.jti.starts_with(API_KEY_SESSION_PREFIX)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,6 +34,132 @@ pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    /// Only admins hand out roles, it can't be picked when registering
+    #[serde(skip_deserializing)]
+    pub role: Role,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
+
+impl Validate for Account {
+    fn check(&self, violations: &mut Violations) {
+        violations.email("email", &self.email);
+        violations.password("password", &self.password, Some(&self.email));
+        check_profile(violations, &self.display_name, &self.bio, &self.avatar_url);
+    }
+}
+
+/// Emails are stored in lowercase, so an account is found however its
+/// email gets typed at the next login
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// The public part of an account, as shown on `/accounts/me`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub id: AccountId,
+    pub email: String,
+    pub role: Role,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email_verified: bool,
+}
+
+/// Replaces the profile fields, the ones left out get cleared
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl Validate for UpdateProfile {
+    fn check(&self, violations: &mut Violations) {
+        check_profile(violations, &self.display_name, &self.bio, &self.avatar_url);
+    }
+}
+
+fn check_profile(
+    violations: &mut Violations,
+    display_name: &Option<String>,
+    bio: &Option<String>,
+    avatar_url: &Option<String>,
+) {
+    if let Some(display_name) = display_name {
+        violations.max_length("display_name", display_name, MAX_DISPLAY_NAME_LENGTH);
+    }
+    if let Some(bio) = bio {
+        violations.max_length("bio", bio, MAX_BIO_LENGTH);
+    }
+    if let Some(avatar_url) = avatar_url.as_deref().filter(|url| !url.is_empty()) {
+        violations.url("avatar_url", avatar_url);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChangePassword {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+impl Validate for ChangePassword {
+    fn check(&self, violations: &mut Violations) {
+        violations.password("new_password", &self.new_password, None);
+    }
+}
+
+/// What an account is allowed to do, every role includes the ones before it
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    const KEYS: [(&'static str, Role); 3] = [
+        ("user", Role::User),
+        ("moderator", Role::Moderator),
+        ("admin", Role::Admin),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(key: &str) -> Result<Role, Error> {
+        Role::KEYS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, role)| *role)
+            .ok_or_else(|| Error::InvalidParameter(format!("`{}` is not a role", key)))
+    }
+}
+
+/// Body of the admin route changing the role of an account
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountRole {
+    pub role: Role,
+}
+
+/// An account as listed to admins, without its password
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountSummary {
+    pub id: AccountId,
+    pub email: String,
+    pub role: Role,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -23,3 +170,85 @@ pub struct NewAccount {
     pub email: String,
     pub password: String,
 }
+
+/// Handed out on login and refresh, the access token goes into the
+/// `Authorization` header and the refresh token is exchanged for a new pair
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Lifetime of the access token in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshToken {
+    pub refresh_token: String,
+}
+
+/// What a token mailed to an account can be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifyEmail {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForgotPassword {
+    pub email: String,
+}
+
+/// Body of `POST /verify-email/resend`, for when the mail sent on
+/// registration got lost or expired
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResendVerification {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResetPassword {
+    pub token: String,
+    pub password: String,
+}
+
+impl Validate for ResetPassword {
+    fn check(&self, violations: &mut Violations) {
+        violations.password("password", &self.password, None);
+    }
+}
+
+/// An account at an OpenID Connect provider, as vouched for by its ID token
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    /// Only a verified email may be linked to an existing account
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+}
+
+#[cfg(test)]
+mod account_tests {
+    use super::normalize_email;
+
+    #[test]
+    fn emails_are_lowercase() {
+        assert_eq!(normalize_email(" Test@Email.COM "), "test@email.com");
+        assert_eq!(normalize_email("test@email.com"), "test@email.com");
+    }
+}
@@ -1,12 +1,23 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::types::question::QuestionId;
+use crate::types::{
+    account::AccountId,
+    question::QuestionId,
+    validation::{Validate, Violations, MAX_CONTENT_LENGTH},
+};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// An answer as it is returned, never read from a request body since
+/// the score is only set by the store
+#[derive(Serialize, Debug, Clone)]
 pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    pub account_id: AccountId,
+    pub created_on: NaiveDateTime,
+    /// Sum of the up and down votes, computed by the store
+    pub score: i64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -17,3 +28,21 @@ pub struct NewAnswer {
     pub content: String,
     pub question_id: QuestionId,
 }
+
+impl Validate for NewAnswer {
+    fn check(&self, violations: &mut Violations) {
+        violations.text("content", &self.content, MAX_CONTENT_LENGTH);
+    }
+}
+
+/// Body of `PUT /answers/{id}`, only the content of an answer can change
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateAnswer {
+    pub content: String,
+}
+
+impl Validate for UpdateAnswer {
+    fn check(&self, violations: &mut Violations) {
+        violations.text("content", &self.content, MAX_CONTENT_LENGTH);
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::NaiveDateTime;
+use handle_errors::Error;
+use serde::{Deserialize, Serialize};
+use warp::http::Method;
+
+use crate::types::{
+    account::{AccountId, Role},
+    validation::{Validate, Violations, MAX_API_KEY_DAYS, MAX_API_KEY_NAME_LENGTH},
+};
+
+/// Start of the `jti` of the sessions standing in for an API key
+pub const API_KEY_SESSION_PREFIX: &str = "api-key-";
+
+/// What requests an API key can be used for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Only `GET` requests
+    Read,
+    /// Any request the account could make
+    Write,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Write => "write",
+        }
+    }
+
+    /// Read keys are limited to `GET` requests
+    pub fn allows(&self, method: &Method) -> bool {
+        match self {
+            ApiKeyScope::Read => method == Method::GET,
+            ApiKeyScope::Write => true,
+        }
+    }
+
+    pub fn parse(key: &str) -> Result<ApiKeyScope, Error> {
+        match key {
+            "read" => Ok(ApiKeyScope::Read),
+            "write" => Ok(ApiKeyScope::Write),
+            _ => Err(Error::InvalidParameter(format!(
+                "`{}` is not an API key scope",
+                key
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiKeyId(pub i32);
+
+/// An API key as listed to its owner, the key itself is only shown once
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub name: String,
+    /// First characters of the key, to tell the keys apart
+    pub prefix: String,
+    pub scope: ApiKeyScope,
+    pub created_on: NaiveDateTime,
+    pub expires_on: Option<NaiveDateTime>,
+    pub last_used_on: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scope: ApiKeyScope,
+    /// The key never expires when left out
+    pub expires_in_days: Option<u32>,
+}
+
+impl Validate for NewApiKey {
+    fn check(&self, violations: &mut Violations) {
+        violations.text("name", &self.name, MAX_API_KEY_NAME_LENGTH);
+        if let Some(days) = self.expires_in_days {
+            if !(1..=MAX_API_KEY_DAYS).contains(&days) {
+                violations.add(
+                    "expires_in_days",
+                    format!("must be between 1 and {}", MAX_API_KEY_DAYS),
+                );
+            }
+        }
+    }
+}
+
+/// Reply to the creation of an API key, the only time `key` is handed out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// What a request made with an API key is allowed to do
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyAccess {
+    pub id: ApiKeyId,
+    pub account_id: AccountId,
+    pub role: Role,
+    pub scope: ApiKeyScope,
+    pub expires_on: Option<NaiveDateTime>,
+}
+
+#[cfg(test)]
+mod api_key_tests {
+    use super::{ApiKeyScope, Method, NewApiKey, Validate, MAX_API_KEY_DAYS};
+
+    fn new_api_key(expires_in_days: Option<u32>) -> NewApiKey {
+        NewApiKey {
+            name: "ci".to_string(),
+            scope: ApiKeyScope::Read,
+            expires_in_days,
+        }
+    }
+
+    #[test]
+    fn read_keys_only_allow_get() {
+        assert!(ApiKeyScope::Read.allows(&Method::GET));
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert!(!ApiKeyScope::Read.allows(&method));
+            assert!(ApiKeyScope::Write.allows(&method));
+        }
+    }
+
+    #[test]
+    fn expiry_has_to_be_in_range() {
+        assert!(new_api_key(None).validate().is_ok());
+        assert!(new_api_key(Some(1)).validate().is_ok());
+        assert!(new_api_key(Some(MAX_API_KEY_DAYS)).validate().is_ok());
+
+        let error = new_api_key(Some(0)).validate().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Invalid fields: expires_in_days must be between 1 and {}", MAX_API_KEY_DAYS)
+        );
+        assert!(new_api_key(Some(u32::MAX)).validate().is_err());
+    }
+}
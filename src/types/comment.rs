@@ -0,0 +1,206 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    account::AccountId,
+    answer::AnswerId,
+    question::QuestionId,
+    validation::{Validate, Violations, MAX_COMMENT_LENGTH},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub id: CommentId,
+    pub content: String,
+    pub account_id: AccountId,
+    pub question_id: Option<QuestionId>,
+    pub answer_id: Option<AnswerId>,
+    /// The comment this one replies to
+    pub parent_id: Option<CommentId>,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommentId(pub i32);
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewComment {
+    pub content: String,
+    pub parent_id: Option<CommentId>,
+}
+
+impl Validate for NewComment {
+    fn check(&self, violations: &mut Violations) {
+        violations.text("content", &self.content, MAX_COMMENT_LENGTH);
+    }
+}
+
+/// Body of `PUT /comments/{id}`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateComment {
+    pub content: String,
+}
+
+impl Validate for UpdateComment {
+    fn check(&self, violations: &mut Violations) {
+        violations.text("content", &self.content, MAX_COMMENT_LENGTH);
+    }
+}
+
+/// The post a comment is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentTarget {
+    Question(i32),
+    Answer(i32),
+}
+
+/// A comment with its replies nested below it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentThread>,
+}
+
+/// Replies deeper than this are listed one after another at this depth
+/// instead of nesting further, so a long back and forth stays readable
+pub const MAX_THREAD_DEPTH: usize = 8;
+
+/// Nests the comments of a post under the comment they reply to, keeping
+/// the order they were given in
+pub fn build_threads(comments: Vec<Comment>) -> Vec<CommentThread> {
+    let ids: HashSet<i32> = comments.iter().map(|comment| comment.id.0).collect();
+    let mut replies: HashMap<i32, Vec<Comment>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for comment in comments {
+        match &comment.parent_id {
+            Some(parent_id) if ids.contains(&parent_id.0) => {
+                replies.entry(parent_id.0).or_default().push(comment)
+            }
+            _ => roots.push(comment),
+        }
+    }
+
+    fn nest(
+        comment: Comment,
+        replies: &mut HashMap<i32, Vec<Comment>>,
+        depth: usize,
+    ) -> CommentThread {
+        let children = replies.remove(&comment.id.0).unwrap_or_default();
+        let children = if depth + 1 < MAX_THREAD_DEPTH {
+            children
+                .into_iter()
+                .map(|child| nest(child, replies, depth + 1))
+                .collect()
+        } else {
+            flatten(children, replies)
+        };
+
+        CommentThread {
+            comment,
+            replies: children,
+        }
+    }
+
+    // Walks the rest of the thread depth first without recursing
+    fn flatten(
+        children: Vec<Comment>,
+        replies: &mut HashMap<i32, Vec<Comment>>,
+    ) -> Vec<CommentThread> {
+        let mut flat = Vec::new();
+        let mut stack: Vec<Comment> = children.into_iter().rev().collect();
+        while let Some(comment) = stack.pop() {
+            if let Some(children) = replies.remove(&comment.id.0) {
+                stack.extend(children.into_iter().rev());
+            }
+            flat.push(CommentThread {
+                comment,
+                replies: Vec::new(),
+            });
+        }
+        flat
+    }
+
+    roots
+        .into_iter()
+        .map(|comment| nest(comment, &mut replies, 0))
+        .collect()
+}
+
+#[cfg(test)]
+mod comment_tests {
+    use super::{build_threads, Comment, CommentId, CommentThread, MAX_THREAD_DEPTH};
+    use crate::types::{account::AccountId, question::QuestionId};
+    use chrono::NaiveDateTime;
+
+    fn comment(id: i32, parent_id: Option<i32>) -> Comment {
+        Comment {
+            id: CommentId(id),
+            content: format!("comment {}", id),
+            account_id: AccountId(1),
+            question_id: Some(QuestionId(1)),
+            answer_id: None,
+            parent_id: parent_id.map(CommentId),
+            created_on: NaiveDateTime::default(),
+        }
+    }
+
+    #[test]
+    fn nests_replies_under_their_parent() {
+        let threads = build_threads(vec![
+            comment(1, None),
+            comment(2, Some(1)),
+            comment(3, None),
+            comment(4, Some(2)),
+            comment(5, Some(1)),
+        ]);
+
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].comment.id, CommentId(1));
+        assert_eq!(
+            threads[0]
+                .replies
+                .iter()
+                .map(|reply| reply.comment.id.0)
+                .collect::<Vec<i32>>(),
+            vec![2, 5]
+        );
+        assert_eq!(threads[0].replies[0].replies[0].comment.id, CommentId(4));
+        assert!(threads[1].replies.is_empty());
+    }
+
+    #[test]
+    fn replies_without_parent_become_roots() {
+        let threads = build_threads(vec![comment(2, Some(1))]);
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].comment.id, CommentId(2));
+    }
+
+    #[test]
+    fn deep_replies_are_listed_flat() {
+        let comments = (1..=12)
+            .map(|id| comment(id, if id == 1 { None } else { Some(id - 1) }))
+            .collect();
+        let threads = build_threads(comments);
+
+        let mut depth = 0;
+        let mut thread: &CommentThread = &threads[0];
+        while thread.replies.len() == 1 {
+            thread = &thread.replies[0];
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_THREAD_DEPTH - 1);
+        assert_eq!(
+            thread
+                .replies
+                .iter()
+                .map(|reply| reply.comment.id.0)
+                .collect::<Vec<i32>>(),
+            (MAX_THREAD_DEPTH as i32 + 1..=12).collect::<Vec<i32>>()
+        );
+        assert!(thread.replies.iter().all(|reply| reply.replies.is_empty()));
+    }
+}
@@ -0,0 +1,123 @@
+use handle_errors::Error;
+use sha2::{Digest, Sha256};
+
+/// Entity tag of a question as `GET /questions/{id}` replies it, the only tag
+/// a question has. It starts with the version and holds a hash of the whole
+/// reply, so answers and votes change it as well. Replies to changes carry the
+/// tag the question has after the change, not one of their own body.
+pub fn reply_etag(version: i32, body: &[u8]) -> String {
+    let hash: String = Sha256::digest(body)[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("\"{}-{}\"", version, hash)
+}
+
+/// The tags an `If-Match` header allows a change to be made on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    /// `*`, any version as long as the resource exists
+    Any,
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    /// Compares the whole tag, the way `If-Match` does
+    pub fn matches(&self, tag: &str) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Tags(tags) => tags.iter().any(|t| t == tag),
+        }
+    }
+}
+
+/// Reads the `If-Match` header changes have to be sent with, so they can't
+/// overwrite edits the client hasn't seen
+/// # Example usage
+/// ```rust
+/// use rust_question_api::types::etag::{extract_if_match, IfMatch};
+///
+/// let if_match = extract_if_match(Some("\"3-5f2b8c1e9a0d4b37\", W/\"4\"")).unwrap();
+/// assert_eq!(if_match, IfMatch::Tags(vec!["\"3-5f2b8c1e9a0d4b37\"".to_string()]));
+/// assert!(extract_if_match(None).is_err());
+/// ```
+pub fn extract_if_match(header: Option<&str>) -> Result<IfMatch, Error> {
+    let header = header.ok_or(Error::PreconditionRequired)?;
+    if header.trim() == "*" {
+        return Ok(IfMatch::Any);
+    }
+
+    // `If-Match` compares strongly, weak tags never match
+    Ok(IfMatch::Tags(
+        entity_tags(header)
+            .filter(|tag| !tag.starts_with("W/"))
+            .map(str::to_string)
+            .collect(),
+    ))
+}
+
+/// Whether the current tag is among the tags of an `If-None-Match` header,
+/// in which case the client already has the reply
+pub fn none_match(header: &str, current: &str) -> bool {
+    header.trim() == "*"
+        || entity_tags(header)
+            .map(|tag| tag.trim_start_matches("W/"))
+            .any(|tag| tag == current)
+}
+
+fn entity_tags(header: &str) -> impl Iterator<Item = &str> {
+    header.split(',').map(str::trim)
+}
+
+#[cfg(test)]
+mod etag_tests {
+    use super::{extract_if_match, none_match, reply_etag, Error, IfMatch};
+
+    #[test]
+    fn if_match_header() {
+        let tag = reply_etag(7, b"{}");
+        assert_eq!(extract_if_match(Some("*")).unwrap(), IfMatch::Any);
+        assert_eq!(
+            extract_if_match(Some(&tag)).unwrap(),
+            IfMatch::Tags(vec![tag.clone()])
+        );
+        assert_eq!(
+            extract_if_match(Some(&format!("W/{}, \"abc\"", tag))).unwrap(),
+            IfMatch::Tags(vec!["\"abc\"".to_string()])
+        );
+        assert!(extract_if_match(Some(&format!("\"2\", {}", tag)))
+            .unwrap()
+            .matches(&tag));
+        assert!(!extract_if_match(Some(&format!("W/{}", tag)))
+            .unwrap()
+            .matches(&tag));
+        // The version alone isn't the tag
+        assert!(!extract_if_match(Some("\"7\"")).unwrap().matches(&tag));
+        assert!(!extract_if_match(Some(&reply_etag(7, b"[]")))
+            .unwrap()
+            .matches(&tag));
+        assert!(matches!(
+            extract_if_match(None),
+            Err(Error::PreconditionRequired)
+        ));
+    }
+
+    #[test]
+    fn if_none_match_header() {
+        assert!(none_match("\"2\", \"3\"", "\"3\""));
+        assert!(none_match("W/\"3\"", "\"3\""));
+        assert!(none_match("*", "\"3\""));
+        assert!(!none_match("\"2\"", "\"3\""));
+        assert!(!none_match("3", "\"3\""));
+    }
+
+    #[test]
+    fn reply_tag_follows_the_whole_reply() {
+        let tag = reply_etag(3, br#"{"answers":[]}"#);
+        assert!(tag.starts_with("\"3-"));
+        assert_eq!(tag, reply_etag(3, br#"{"answers":[]}"#));
+        assert_ne!(tag, reply_etag(3, br#"{"answers":[{"id":1}]}"#));
+        assert!(none_match(&tag, &tag));
+        assert!(!none_match("\"3\"", &tag));
+    }
+}
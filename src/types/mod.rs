@@ -1,4 +1,12 @@
 pub mod answer;
+pub mod api_key;
+pub mod comment;
+pub mod etag;
 pub mod pagination;
 pub mod question;
-pub mod account;
\ No newline at end of file
+pub mod revision;
+pub mod account;
+pub mod tag;
+pub mod two_factor;
+pub mod validation;
+pub mod vote;
\ No newline at end of file
@@ -1,92 +1,298 @@
 use std::collections::HashMap;
 
+use chrono::NaiveDateTime;
 use handle_errors::Error;
+use serde::Serialize;
 
-/// Pagination struct which is getting extract
+const CURSOR_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Paging and ordering of a listing which is getting extract
 /// from query params
 #[derive(Default, Debug, PartialEq)]
-pub struct Pagination {
+pub struct ListQuery {
     /// The index of the last item which has to be returned
     pub limit: Option<u32>,
     /// The index of the first item which has to be returned
     pub offset: u32,
+    /// Position after which the next page starts, replaces `offset`
+    pub cursor: Option<Cursor>,
+    /// Order of the items, each listing picks its own default
+    pub sort: Option<Sort>,
+}
+
+/// Allowed values of the `sort` query param
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Newest,
+    Oldest,
+    MostAnswered,
+    MostVoted,
+    RecentlyActive,
+}
+
+impl Sort {
+    const KEYS: [(&'static str, Sort); 5] = [
+        ("newest", Sort::Newest),
+        ("oldest", Sort::Oldest),
+        ("most_answered", Sort::MostAnswered),
+        ("most_voted", Sort::MostVoted),
+        ("recently_active", Sort::RecentlyActive),
+    ];
+
+    pub fn parse(key: &str) -> Result<Sort, Error> {
+        Sort::KEYS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, sort)| *sort)
+            .ok_or_else(|| {
+                Error::InvalidParameter(format!(
+                    "sort must be one of {}, got `{}`",
+                    Sort::KEYS
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<&str>>()
+                        .join(", "),
+                    key
+                ))
+            })
+    }
+
+    /// Only listings ordered by creation date can be paged with a cursor
+    pub fn supports_cursor(&self) -> bool {
+        matches!(self, Sort::Newest | Sort::Oldest)
+    }
+}
+
+impl ListQuery {
+    /// Rejects the params which only make sense for the `/questions` listing
+    pub fn ensure_offset_only(&self) -> Result<(), Error> {
+        if self.cursor.is_some() || self.sort.is_some() {
+            return Err(Error::InvalidParameter(
+                "this listing only supports limit and offset".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Position of an item in a listing ordered by creation date,
+/// handed out to clients as an opaque string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_on: NaiveDateTime,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        base64::encode_config(
+            format!("{}|{}", self.created_on.format(CURSOR_TIME_FORMAT), self.id),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Result<Cursor, Error> {
+        let invalid = || Error::InvalidParameter("cursor is not valid".to_string());
+
+        let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(invalid)?;
+        let (created_on, id) = decoded.split_once('|').ok_or_else(invalid)?;
+
+        Ok(Cursor {
+            created_on: NaiveDateTime::parse_from_str(created_on, CURSOR_TIME_FORMAT)
+                .map_err(|_| invalid())?,
+            id: id.parse::<i32>().map_err(Error::ParseError)?,
+        })
+    }
+}
+
+/// A page of a listing along with what is needed to fetch the next one
+#[derive(Debug, Serialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to get the following page, if the listing supports it
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    /// Number of items in the whole listing, across all pages
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    /// Builds a page out of the items fetched with `limit + 1`, the extra item
+    /// only tells whether there is another page and gets dropped
+    pub fn new(mut items: Vec<T>, limit: Option<u32>, total: i64) -> Page<T> {
+        let has_more = matches!(limit, Some(limit) if items.len() > limit as usize);
+        if let Some(limit) = limit {
+            items.truncate(limit as usize);
+        }
+
+        Page {
+            items,
+            next_cursor: None,
+            has_more,
+            total,
+        }
+    }
+
+    /// Same as `Page::new`, pointing `next_cursor` after the last item
+    pub fn with_cursors(items: Vec<(T, Cursor)>, limit: Option<u32>, total: i64) -> Page<T> {
+        let page = Page::new(items, limit, total);
+        let next_cursor = match page.items.last() {
+            Some((_, cursor)) if page.has_more => Some(cursor.encode()),
+            _ => None,
+        };
+
+        Page {
+            items: page.items.into_iter().map(|(item, _)| item).collect(),
+            next_cursor,
+            has_more: page.has_more,
+            total,
+        }
+    }
 }
 
 /// Extract query parameters from the `/questions` route
 /// # Example query
 /// GET requests to this route can have a pagination attached so we just
-/// return the questions we need
-/// `/questions?start=1&end=10`
+/// return the questions we need. Either an `offset` or a `cursor` taken from
+/// the `next_cursor` of a previous page tells where the page starts.
+/// `/questions?limit=10&offset=20&sort=most_answered`
+/// `/questions?limit=10&cursor=MjAyMy0wNC0xMlQxMDowMDowMHwx`
 /// # Example usage
 /// ```rust
 /// use std::collections::HashMap;
-/// use rust_question_api::types::pagination::extract_pagination;
-/// 
+/// use rust_question_api::types::pagination::{extract_list_query, Sort};
+///
 /// let mut query = HashMap::new();
 /// query.insert("limit".to_string(), "1".to_string());
 /// query.insert("offset".to_string(), "10".to_string());
-/// let p = extract_pagination(query).unwrap();
+/// query.insert("sort".to_string(), "oldest".to_string());
+/// let p = extract_list_query(&query).unwrap();
 /// assert_eq!(p.limit, Some(1));
 /// assert_eq!(p.offset, 10);
+/// assert_eq!(p.cursor, None);
+/// assert_eq!(p.sort, Some(Sort::Oldest));
 /// ```
-pub fn extract_pagination(params: HashMap<String, String>) -> Result<Pagination, Error> {
-    // Could be improved in the future
-    if params.contains_key("limit") && params.contains_key("offset") {
-        return Ok(Pagination {
-            // Takes the "limit" parameter in the query
-            // and tries to convert it to a number
-            limit: Some(
-                params
-                    .get("limit")
-                    .unwrap()
-                    .parse::<u32>()
-                    .map_err(Error::ParseError)?,
-            ),
-            // Takes the "offset" parameter in the query
-            // and tries to convert it to a number
-            offset: params
-                .get("offset")
-                .unwrap()
-                .parse::<u32>()
-                .map_err(Error::ParseError)?,
-        });
+pub fn extract_list_query(params: &HashMap<String, String>) -> Result<ListQuery, Error> {
+    if params.contains_key("offset") && params.contains_key("cursor") {
+        return Err(Error::InvalidParameter(
+            "offset and cursor cannot be combined".to_string(),
+        ));
     }
 
-    Err(Error::MissingParameters)
+    let sort = params.get("sort").map(|sort| Sort::parse(sort)).transpose()?;
+    if params.contains_key("cursor") && matches!(sort, Some(sort) if !sort.supports_cursor()) {
+        return Err(Error::InvalidParameter(
+            "cursor can only be used when sorting by newest or oldest".to_string(),
+        ));
+    }
+
+    Ok(ListQuery {
+        // Takes the "limit" parameter in the query
+        // and tries to convert it to a number
+        limit: params
+            .get("limit")
+            .map(|limit| limit.parse::<u32>())
+            .transpose()
+            .map_err(Error::ParseError)?,
+        // Takes the "offset" parameter in the query
+        // and tries to convert it to a number
+        offset: params
+            .get("offset")
+            .map(|offset| offset.parse::<u32>())
+            .transpose()
+            .map_err(Error::ParseError)?
+            .unwrap_or_default(),
+        cursor: params
+            .get("cursor")
+            .map(|cursor| Cursor::decode(cursor))
+            .transpose()?,
+        sort,
+    })
 }
 
 #[cfg(test)]
 mod pagination_tests {
-    use super::{extract_pagination, HashMap, Pagination, Error};
+    use super::{extract_list_query, Cursor, Error, HashMap, ListQuery, NaiveDateTime, Sort};
 
     #[test]
     fn valid_pagination() {
         let mut params = HashMap::new();
         params.insert(String::from("limit"), String::from("1"));
         params.insert(String::from("offset"), String::from("1"));
-        let pagination_result = extract_pagination(params);
-        let expected = Pagination {
+        let pagination_result = extract_list_query(&params);
+        let expected = ListQuery {
             limit: Some(1),
             offset: 1,
+            cursor: None,
+            sort: None,
         };
         assert_eq!(pagination_result.unwrap(), expected);
     }
 
     #[test]
-    fn missing_offset_parameter() {
+    fn offset_defaults_to_zero() {
+        let mut params = HashMap::new();
+        params.insert(String::from("limit"), String::from("1"));
+
+        let pagination_result = extract_list_query(&params).unwrap();
+
+        assert_eq!(pagination_result.limit, Some(1));
+        assert_eq!(pagination_result.offset, 0);
+    }
+
+    #[test]
+    fn cursor_and_offset_parameters() {
+        let cursor = Cursor {
+            created_on: NaiveDateTime::parse_from_str(
+                "2023-04-12 10:00:00.123456",
+                "%Y-%m-%d %H:%M:%S%.f",
+            )
+            .unwrap(),
+            id: 42,
+        };
+
         let mut params = HashMap::new();
         params.insert(String::from("limit"), String::from("1"));
+        params.insert(String::from("cursor"), cursor.encode());
+        assert_eq!(extract_list_query(&params).unwrap().cursor, Some(cursor));
 
-        let pagination_result = format!(
+        params.insert(String::from("offset"), String::from("1"));
+        let pagination_result = format!("{}", extract_list_query(&params).unwrap_err());
+        let expected = format!(
             "{}",
-            extract_pagination(params).unwrap_err()
+            Error::InvalidParameter("offset and cursor cannot be combined".to_string())
         );
+        assert_eq!(pagination_result, expected);
+    }
 
-        let expected = format!("{}", Error::MissingParameters);
+    #[test]
+    fn sort_parameter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("sort"), String::from("most_answered"));
+        assert_eq!(
+            extract_list_query(&params).unwrap().sort,
+            Some(Sort::MostAnswered)
+        );
 
-        assert_eq!(pagination_result, expected);
+        params.insert(String::from("sort"), String::from("random"));
+        assert!(extract_list_query(&params).is_err());
     }
 
+    #[test]
+    fn cursor_with_unsupported_sort() {
+        let mut params = HashMap::new();
+        params.insert(String::from("sort"), String::from("recently_active"));
+        params.insert(String::from("cursor"), String::from("MjAyMy0wNC0xMlQxMDowMDowMHwx"));
+        assert!(extract_list_query(&params).is_err());
+    }
 
-    
+    #[test]
+    fn invalid_cursor() {
+        let mut params = HashMap::new();
+        params.insert(String::from("cursor"), String::from("not a cursor"));
+        assert!(extract_list_query(&params).is_err());
+    }
 }
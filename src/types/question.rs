@@ -1,6 +1,14 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
-use serde::{Deserialize, Serialize};
+use handle_errors::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::types::{
+    answer::{Answer, AnswerId},
+    tag::TagMatch,
+    validation::{Validate, Violations, MAX_CONTENT_LENGTH, MAX_TITLE_LENGTH},
+};
 
 #[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
 pub struct QuestionId(pub i32);
@@ -11,12 +19,20 @@ impl Display for QuestionId {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A question as it is returned, never read from a request body since
+/// the score, accepted answer and version are only set by the store
+#[derive(Debug, Serialize, Clone)]
 pub struct Question {
     pub id: QuestionId,
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    /// Sum of the up and down votes, computed by the store
+    pub score: i64,
+    /// The answer the author of the question marked as the solution
+    pub accepted_answer_id: Option<AnswerId>,
+    /// Goes up with every change, sent as the `ETag` of the question
+    pub version: i32,
 }
 
 impl Display for Question {
@@ -35,3 +51,264 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+impl Validate for NewQuestion {
+    fn check(&self, violations: &mut Violations) {
+        check_question(violations, &self.title, &self.content, &self.tags);
+    }
+}
+
+/// Body of `PUT /questions/{id}`, the id is taken from the path
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct UpdateQuestion {
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+}
+
+impl Validate for UpdateQuestion {
+    fn check(&self, violations: &mut Violations) {
+        check_question(violations, &self.title, &self.content, &self.tags);
+    }
+}
+
+/// Body of `PATCH /questions/{id}`, a JSON Merge Patch (RFC 7396):
+/// fields left out stay as they are and `null` removes a field
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct QuestionPatch {
+    #[serde(default, deserialize_with = "present")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub content: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub tags: Option<Option<Vec<String>>>,
+}
+
+impl QuestionPatch {
+    /// The question as it is after the patch
+    pub fn apply(self, question: &Question) -> UpdateQuestion {
+        UpdateQuestion {
+            title: self
+                .title
+                .flatten()
+                .unwrap_or_else(|| question.title.clone()),
+            content: self
+                .content
+                .flatten()
+                .unwrap_or_else(|| question.content.clone()),
+            tags: self.tags.unwrap_or_else(|| question.tags.clone()),
+        }
+    }
+}
+
+impl Validate for QuestionPatch {
+    fn check(&self, violations: &mut Violations) {
+        // A question can lose its tags, but not its title or content
+        match &self.title {
+            Some(Some(title)) => violations.text("title", title, MAX_TITLE_LENGTH),
+            Some(None) => violations.add("title", "must not be null"),
+            None => (),
+        }
+        match &self.content {
+            Some(Some(content)) => violations.text("content", content, MAX_CONTENT_LENGTH),
+            Some(None) => violations.add("content", "must not be null"),
+            None => (),
+        }
+        if let Some(Some(tags)) = &self.tags {
+            violations.tags("tags", tags);
+        }
+    }
+}
+
+/// Tells a field set to `null`, `Some(None)`, apart from one left out, `None`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn check_question(
+    violations: &mut Violations,
+    title: &str,
+    content: &str,
+    tags: &Option<Vec<String>>,
+) {
+    violations.text("title", title, MAX_TITLE_LENGTH);
+    violations.text("content", content, MAX_CONTENT_LENGTH);
+    if let Some(tags) = tags {
+        violations.tags("tags", tags);
+    }
+}
+
+/// A single question with all of its answers embedded,
+/// returned by `GET /questions/{id}`
+#[derive(Debug, Serialize, Clone)]
+pub struct QuestionWithAnswers {
+    #[serde(flatten)]
+    pub question: Question,
+    pub answers: Vec<Answer>,
+}
+
+/// A question matched by a full-text search, along with its relevance
+/// rank and an excerpt of the matching text with the hits highlighted
+#[derive(Debug, Serialize, Clone)]
+pub struct QuestionMatch {
+    #[serde(flatten)]
+    pub question: Question,
+    pub rank: f32,
+    pub snippet: String,
+}
+
+/// Filters which can be applied to the `/questions` listing
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QuestionFilter {
+    /// Only return questions tagged with these tags
+    pub tags: Option<Vec<String>>,
+    /// Whether a question needs any or all of the `tags`
+    pub tag_match: TagMatch,
+    /// Only return questions with (`true`) or without (`false`) an accepted answer
+    pub answered: Option<bool>,
+}
+
+/// Extract the question filters from the query params of the `/questions` route
+/// # Example query
+/// `/questions?tags=rust,warp&match=all&answered=false`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_question_api::types::{question::extract_question_filter, tag::TagMatch};
+///
+/// let mut query = HashMap::new();
+/// query.insert("tags".to_string(), "Rust, warp".to_string());
+/// query.insert("match".to_string(), "all".to_string());
+/// let f = extract_question_filter(&query).unwrap();
+/// assert_eq!(f.tags, Some(vec!["rust".to_string(), "warp".to_string()]));
+/// assert_eq!(f.tag_match, TagMatch::All);
+/// ```
+pub fn extract_question_filter(params: &HashMap<String, String>) -> Result<QuestionFilter, Error> {
+    let tags = params.get("tags").map(|tags| {
+        tags.split(',')
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<String>>()
+    });
+
+    let tag_match = match params.get("match").map(String::as_str) {
+        None | Some("any") => TagMatch::Any,
+        Some("all") => TagMatch::All,
+        Some(other) => {
+            return Err(Error::InvalidParameter(format!(
+                "match must be `any` or `all`, got `{}`",
+                other
+            )))
+        }
+    };
+
+    let answered = match params.get("answered").map(String::as_str) {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(other) => {
+            return Err(Error::InvalidParameter(format!(
+                "answered must be `true` or `false`, got `{}`",
+                other
+            )))
+        }
+    };
+
+    Ok(QuestionFilter {
+        tags: tags.filter(|tags| !tags.is_empty()),
+        tag_match,
+        answered,
+    })
+}
+
+#[cfg(test)]
+mod question_patch_tests {
+    use super::{AnswerId, Question, QuestionId, QuestionPatch, UpdateQuestion};
+    use crate::types::validation::Validate;
+
+    fn question() -> Question {
+        Question {
+            id: QuestionId(1),
+            title: "Old title".to_string(),
+            content: "Old content".to_string(),
+            tags: Some(vec!["rust".to_string()]),
+            score: 3,
+            accepted_answer_id: Some(AnswerId(2)),
+            version: 4,
+        }
+    }
+
+    #[test]
+    fn fields_left_out_stay() {
+        let patch: QuestionPatch = serde_json::from_str(r#"{"title": "New title"}"#).unwrap();
+        assert_eq!(
+            patch.apply(&question()),
+            UpdateQuestion {
+                title: "New title".to_string(),
+                content: "Old content".to_string(),
+                tags: Some(vec!["rust".to_string()]),
+            }
+        );
+    }
+
+    #[test]
+    fn null_removes_tags() {
+        let patch: QuestionPatch = serde_json::from_str(r#"{"tags": null}"#).unwrap();
+        assert!(patch.validate().is_ok());
+        assert_eq!(patch.apply(&question()).tags, None);
+    }
+
+    #[test]
+    fn title_and_content_cannot_be_removed() {
+        let patch: QuestionPatch =
+            serde_json::from_str(r#"{"title": null, "content": null}"#).unwrap();
+        assert!(patch.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod question_filter_tests {
+    use super::{extract_question_filter, HashMap, QuestionFilter, TagMatch};
+
+    #[test]
+    fn no_filter() {
+        let params = HashMap::new();
+        assert_eq!(
+            extract_question_filter(&params).unwrap(),
+            QuestionFilter::default()
+        );
+    }
+
+    #[test]
+    fn empty_tags_are_ignored() {
+        let mut params = HashMap::new();
+        params.insert(String::from("tags"), String::from(" ,,"));
+        assert_eq!(extract_question_filter(&params).unwrap().tags, None);
+    }
+
+    #[test]
+    fn answered_parameter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("answered"), String::from("false"));
+        assert_eq!(extract_question_filter(&params).unwrap().answered, Some(false));
+        params.insert(String::from("answered"), String::from("yes"));
+        assert!(extract_question_filter(&params).is_err());
+    }
+
+    #[test]
+    fn invalid_match_parameter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("tags"), String::from("rust"));
+        params.insert(String::from("match"), String::from("some"));
+        assert!(extract_question_filter(&params).is_err());
+        params.insert(String::from("match"), String::from("any"));
+        assert_eq!(
+            extract_question_filter(&params).unwrap().tag_match,
+            TagMatch::Any
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::types::{account::AccountId, answer::AnswerId, question::QuestionId};
+
+/// State of a question after one of its edits, the first revision
+/// being the question as it was posted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuestionRevision {
+    pub question_id: QuestionId,
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    /// The account which made the edit
+    pub account_id: AccountId,
+    pub created_on: NaiveDateTime,
+}
+
+/// State of an answer after one of its edits
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnswerRevision {
+    pub answer_id: AnswerId,
+    pub revision: i32,
+    pub content: String,
+    pub account_id: AccountId,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// Line by line changes between two revisions of a question
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub title: Vec<DiffLine>,
+    pub content: Vec<DiffLine>,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                ChangeTag::Equal => DiffOp::Equal,
+                ChangeTag::Insert => DiffOp::Insert,
+                ChangeTag::Delete => DiffOp::Delete,
+            },
+            text: change.value().trim_end_matches('\n').to_string(),
+        })
+        .collect()
+}
+
+pub fn diff_revisions(from: &QuestionRevision, to: &QuestionRevision) -> RevisionDiff {
+    let from_tags = from.tags.clone().unwrap_or_default();
+    let to_tags = to.tags.clone().unwrap_or_default();
+
+    RevisionDiff {
+        from: from.revision,
+        to: to.revision,
+        title: diff_lines(&from.title, &to.title),
+        content: diff_lines(&from.content, &to.content),
+        tags_added: to_tags
+            .iter()
+            .filter(|tag| !from_tags.contains(tag))
+            .cloned()
+            .collect(),
+        tags_removed: from_tags
+            .iter()
+            .filter(|tag| !to_tags.contains(tag))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod revision_tests {
+    use super::{diff_revisions, DiffLine, DiffOp, QuestionRevision};
+    use crate::types::{account::AccountId, question::QuestionId};
+    use chrono::NaiveDateTime;
+
+    fn revision(revision: i32, content: &str, tags: &[&str]) -> QuestionRevision {
+        QuestionRevision {
+            question_id: QuestionId(1),
+            revision,
+            title: "Title".to_string(),
+            content: content.to_string(),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            account_id: AccountId(1),
+            created_on: NaiveDateTime::default(),
+        }
+    }
+
+    #[test]
+    fn diff_between_revisions() {
+        let diff = diff_revisions(
+            &revision(1, "first line\nsecond line", &["rust", "warp"]),
+            &revision(3, "first line\nchanged line", &["rust", "tokio"]),
+        );
+
+        assert_eq!((diff.from, diff.to), (1, 3));
+        assert_eq!(
+            diff.title,
+            vec![DiffLine {
+                op: DiffOp::Equal,
+                text: "Title".to_string()
+            }]
+        );
+        assert_eq!(
+            diff.content
+                .iter()
+                .map(|line| (line.op, line.text.as_str()))
+                .collect::<Vec<(DiffOp, &str)>>(),
+            vec![
+                (DiffOp::Equal, "first line"),
+                (DiffOp::Delete, "second line"),
+                (DiffOp::Insert, "changed line"),
+            ]
+        );
+        assert_eq!(diff.tags_added, vec!["tokio".to_string()]);
+        assert_eq!(diff.tags_removed, vec!["warp".to_string()]);
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// A tag along with the number of questions carrying it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub count: i64,
+}
+
+/// Tags are stored in lowercase, the way the `tags` filter of `/questions`
+/// and the tag suggestions look them up
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    tags.into_iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .collect()
+}
+
+/// How a list of tags in a query has to match the tags of a question
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TagMatch {
+    /// The question carries at least one of the tags
+    #[default]
+    Any,
+    /// The question carries every one of the tags
+    All,
+}
+
+#[cfg(test)]
+mod tag_tests {
+    use super::normalize_tags;
+
+    #[test]
+    fn tags_are_lowercased() {
+        assert_eq!(
+            normalize_tags(vec!["Rust".to_string(), " WARP ".to_string()]),
+            vec!["rust".to_string(), "warp".to_string()]
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Two-factor settings of an account as stored, the secret is encrypted
+#[derive(Debug, Clone)]
+pub struct TwoFactor {
+    pub secret: String,
+    /// Codes are only asked for at login once the enrollment is confirmed
+    pub confirmed: bool,
+    /// Time step of the last code used, so no code works twice
+    pub last_used_step: Option<i64>,
+}
+
+/// Reply to the enrollment, the only time the secret and the recovery codes are shown
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TwoFactorEnrollment {
+    pub otpauth_uri: String,
+    /// The secret of `otpauth_uri`, for apps which can't scan it
+    pub secret: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// A 6-digit code of the authenticator app, or a recovery code
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+/// Reply to the login of an account with two-factor authentication,
+/// the token gets traded for a token pair together with a code
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginChallenge {
+    pub challenge_token: String,
+    /// Lifetime of the challenge token in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifyLoginChallenge {
+    pub challenge_token: String,
+    pub code: String,
+}
@@ -0,0 +1,241 @@
+use handle_errors::{Error, FieldError};
+
+/// Longest question title, the size of the `title` column
+pub const MAX_TITLE_LENGTH: usize = 255;
+/// Longest text of a question or an answer
+pub const MAX_CONTENT_LENGTH: usize = 30_000;
+pub const MAX_COMMENT_LENGTH: usize = 2_000;
+pub const MAX_TAGS: usize = 5;
+pub const MAX_TAG_LENGTH: usize = 35;
+/// Longest email, the size of the `email` column
+pub const MAX_EMAIL_LENGTH: usize = 255;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// Hashing is slow on purpose, so very long passwords are turned down
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 100;
+pub const MAX_BIO_LENGTH: usize = 2_000;
+pub const MAX_URL_LENGTH: usize = 2_048;
+/// Longest API key name, the size of the `name` column
+pub const MAX_API_KEY_NAME_LENGTH: usize = 100;
+/// Longest an API key can be valid for, about ten years
+pub const MAX_API_KEY_DAYS: u32 = 3_650;
+
+/// Checks of a request body which its type alone can't express,
+/// run by the handlers before anything gets stored
+pub trait Validate {
+    /// Records every problem of the body, not only the first one
+    fn check(&self, violations: &mut Violations);
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut violations = Violations::default();
+        self.check(&mut violations);
+
+        if violations.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(violations.0))
+        }
+    }
+}
+
+/// The fields which didn't pass validation, collected by `Validate::check`
+#[derive(Debug, Default)]
+pub struct Violations(Vec<FieldError>);
+
+impl Violations {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// A text which has to be there, made of more than whitespace
+    pub fn text(&mut self, field: &str, value: &str, max: usize) {
+        if value.trim().is_empty() {
+            self.add(field, "must not be empty");
+        } else {
+            self.max_length(field, value, max);
+        }
+    }
+
+    pub fn max_length(&mut self, field: &str, value: &str, max: usize) {
+        if value.chars().count() > max {
+            self.add(field, format!("must be at most {} characters long", max));
+        }
+    }
+
+    pub fn email(&mut self, field: &str, email: &str) {
+        if email.chars().count() > MAX_EMAIL_LENGTH {
+            self.max_length(field, email, MAX_EMAIL_LENGTH);
+        } else if !is_valid_email(email) {
+            self.add(field, "must be a valid email address");
+        }
+    }
+
+    /// Passwords need some length and must not give away the account they belong to
+    pub fn password(&mut self, field: &str, password: &str, email: Option<&str>) {
+        let length = password.chars().count();
+
+        if length < MIN_PASSWORD_LENGTH {
+            self.add(
+                field,
+                format!("must be at least {} characters long", MIN_PASSWORD_LENGTH),
+            );
+        } else if length > MAX_PASSWORD_LENGTH {
+            self.max_length(field, password, MAX_PASSWORD_LENGTH);
+        } else if password.chars().all(|c| password.starts_with(c)) {
+            self.add(field, "must not repeat a single character");
+        } else if email.is_some_and(|email| password.eq_ignore_ascii_case(email)) {
+            self.add(field, "must not be the email address");
+        }
+    }
+
+    /// Tags are checked after `normalize_tags` turned them lowercase
+    pub fn tags(&mut self, field: &str, tags: &[String]) {
+        if tags.len() > MAX_TAGS {
+            self.add(field, format!("must not have more than {} tags", MAX_TAGS));
+        }
+
+        for (i, tag) in tags.iter().enumerate() {
+            let field = format!("{}[{}]", field, i);
+            if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+                self.add(
+                    &field,
+                    format!("must be 1 to {} characters long", MAX_TAG_LENGTH),
+                );
+            } else if !is_valid_tag(tag) {
+                self.add(
+                    &field,
+                    "must start with a lowercase letter or digit and only contain those or + # . -",
+                );
+            } else if tags[..i].contains(tag) {
+                self.add(&field, "is a duplicate");
+            }
+        }
+    }
+
+    pub fn url(&mut self, field: &str, url: &str) {
+        if url.chars().count() > MAX_URL_LENGTH {
+            self.max_length(field, url, MAX_URL_LENGTH);
+        } else if !matches!(
+            reqwest::Url::parse(url)
+                .map(|url| url.scheme().to_string())
+                .as_deref(),
+            Ok("http" | "https")
+        ) {
+            self.add(field, "must be an http or https URL");
+        }
+    }
+}
+
+/// Only the syntax of the address, whether it exists is checked by mailing it
+fn is_valid_email(email: &str) -> bool {
+    let (local, domain) = match email.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    !local.is_empty()
+        && !local.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    tag.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+#.-".contains(c))
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::{is_valid_email, Error, Validate, Violations};
+
+    struct Body {
+        title: String,
+        password: String,
+        tags: Vec<String>,
+    }
+
+    impl Validate for Body {
+        fn check(&self, violations: &mut Violations) {
+            violations.text("title", &self.title, 10);
+            violations.password("password", &self.password, Some("tess@example.com"));
+            violations.tags("tags", &self.tags);
+        }
+    }
+
+    fn fields(body: Body) -> Vec<String> {
+        match body.validate() {
+            Ok(()) => Vec::new(),
+            Err(Error::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let valid = Body {
+            title: "A question".to_string(),
+            password: "correct horse".to_string(),
+            tags: vec!["rust".to_string(), "c++".to_string()],
+        };
+        assert!(valid.validate().is_ok());
+
+        let invalid = Body {
+            title: "   ".to_string(),
+            password: "short".to_string(),
+            tags: vec!["Rust".to_string(), "warp".to_string(), "warp".to_string()],
+        };
+        assert_eq!(
+            fields(invalid),
+            vec!["title", "password", "tags[0]", "tags[2]"]
+        );
+    }
+
+    #[test]
+    fn length_limits_count_characters() {
+        let body = |title: &str, tags: usize| Body {
+            title: title.to_string(),
+            password: "correct horse".to_string(),
+            tags: (0..tags).map(|i| format!("tag{}", i)).collect(),
+        };
+
+        assert!(fields(body("ääääääääää", 5)).is_empty());
+        assert_eq!(fields(body("äääääääääää", 6)), vec!["title", "tags"]);
+    }
+
+    #[test]
+    fn password_policy() {
+        let body = |password: &str| Body {
+            title: "A question".to_string(),
+            password: password.to_string(),
+            tags: Vec::new(),
+        };
+
+        assert_eq!(fields(body("aaaaaaaaaa")), vec!["password"]);
+        assert_eq!(fields(body("Tess@Example.com")), vec!["password"]);
+        assert_eq!(fields(body(&"ab".repeat(65))), vec!["password"]);
+        assert!(fields(body("password")).is_empty());
+    }
+
+    #[test]
+    fn email_syntax() {
+        assert!(is_valid_email("tess@example.com"));
+        assert!(is_valid_email("tess.o+qa@mail.example.co.uk"));
+        assert!(!is_valid_email("tess"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("tess@localhost"));
+        assert!(!is_valid_email("tess@exa mple.com"));
+        assert!(!is_valid_email("tess@example..com"));
+        assert!(!is_valid_email("te@ss@example.com"));
+    }
+}
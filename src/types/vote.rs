@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    /// What the vote adds to the score of the post
+    pub fn value(&self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewVote {
+    pub direction: VoteDirection,
+}
+
+/// Score of a question or answer after a vote was cast or withdrawn
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Score {
+    pub score: i64,
+}
+
+/// The post a vote is cast on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteTarget {
+    Question(i32),
+    Answer(i32),
+}
+
+#[cfg(test)]
+mod vote_tests {
+    use super::{NewVote, VoteDirection};
+
+    #[test]
+    fn vote_direction_from_body() {
+        let vote: NewVote = serde_json::from_str(r#"{"direction": "down"}"#).unwrap();
+        assert_eq!(vote.direction, VoteDirection::Down);
+        assert!(serde_json::from_str::<NewVote>(r#"{"direction": "sideways"}"#).is_err());
+        assert!(serde_json::from_str::<NewVote>(r#"{"direction": "Up"}"#).is_err());
+    }
+
+    #[test]
+    fn votes_add_one_point_either_way() {
+        assert_eq!(VoteDirection::Up.value(), 1);
+        assert_eq!(VoteDirection::Down.value(), -1);
+    }
+}